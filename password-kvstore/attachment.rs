@@ -0,0 +1,223 @@
+//! Binary attachments of an [`Entry`](crate::Entry).
+//!
+//! An attachment is encrypted with the STREAM construction of
+//! `chacha20poly1305` in chunks of [`ATTACHMENT_CHUNK_SIZE`] bytes under a
+//! random key of its own, so that arbitrarily large files are encrypted
+//! and decrypted from one reader into one writer without being held in
+//! memory. The key lives in the [`Attachment`] record of the entry,
+//! which is sealed with the rest of the entry, and the ciphertext is
+//! stored separately, see [`Vault::add_attachment`](crate::Vault::add_attachment).
+//!
+//! Encrypted layout: [`ATTACHMENT_MAGIC`], the 7 byte STREAM nonce prefix,
+//! then every chunk's ciphertext and tag. Truncation and reordering of
+//! chunks fail authentication.
+use std::io::{ErrorKind, Read, Write};
+
+use chacha20poly1305::aead::stream::{DecryptorBE32, EncryptorBE32};
+use chacha20poly1305::aead::KeyInit;
+use chacha20poly1305::ChaCha20Poly1305;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::{Error, Result, Secret};
+
+pub const ATTACHMENT_MAGIC: &[u8; 4] = b"PKVA";
+pub const ATTACHMENT_CHUNK_SIZE: usize = 64 * 1024;
+const NONCE_PREFIX_LENGTH: usize = 7;
+const TAG_LENGTH: usize = 16;
+
+/// Record of an encrypted attachment, kept in [`Entry::attachments`](crate::Entry::attachments).
+///
+/// `id` names the encrypted file, `name` is the original file name and
+/// `size` the plaintext length in bytes.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Attachment {
+    pub id: String,
+    pub name: String,
+    pub size: u64,
+    key: Secret,
+}
+impl Attachment {
+    /// Encrypts everything `reader` yields into `writer` under a fresh key.
+    pub fn encrypt(
+        name: &str,
+        mut reader: impl Read,
+        mut writer: impl Write,
+    ) -> Result<Attachment> {
+        let mut id = [0u8; 16];
+        rand::rng().fill_bytes(&mut id);
+        let mut key = vec![0u8; 32];
+        rand::rng().fill_bytes(&mut key);
+        let key = Secret::from(key);
+        let mut nonce = [0u8; NONCE_PREFIX_LENGTH];
+        rand::rng().fill_bytes(&mut nonce);
+        let mut encryptor = EncryptorBE32::from_aead(cipher(&key, name)?, nonce.as_slice().into());
+        writer.write_all(ATTACHMENT_MAGIC)?;
+        writer.write_all(&nonce)?;
+
+        let mut size = 0u64;
+        let mut chunk = Zeroizing::new(vec![0u8; ATTACHMENT_CHUNK_SIZE]);
+        let mut next = Zeroizing::new(vec![0u8; ATTACHMENT_CHUNK_SIZE]);
+        let mut length = read_chunk(&mut reader, &mut chunk)?;
+        loop {
+            let next_length = match length {
+                ATTACHMENT_CHUNK_SIZE => read_chunk(&mut reader, &mut next)?,
+                _ => 0,
+            };
+            size += length as u64;
+            if next_length == 0 {
+                writer.write_all(&encryptor.encrypt_last(&chunk[..length])?)?;
+                break;
+            }
+            writer.write_all(&encryptor.encrypt_next(&chunk[..length])?)?;
+            std::mem::swap(&mut chunk, &mut next);
+            length = next_length;
+        }
+        writer.flush()?;
+        Ok(Attachment {
+            id: hex::encode(id),
+            name: name.to_string(),
+            size,
+            key,
+        })
+    }
+
+    /// Decrypts the output of [`encrypt`](Attachment::encrypt) from
+    /// `reader` into `writer`, returning the number of bytes written.
+    ///
+    /// Chunks are authenticated before they are written, but a chunk
+    /// that fails authentication aborts the copy halfway, so `writer`
+    /// should be discarded on error.
+    pub fn decrypt(&self, mut reader: impl Read, mut writer: impl Write) -> Result<u64> {
+        let mut header = [0u8; ATTACHMENT_MAGIC.len() + NONCE_PREFIX_LENGTH];
+        if read_chunk(&mut reader, &mut header)? != header.len()
+            || &header[..ATTACHMENT_MAGIC.len()] != ATTACHMENT_MAGIC
+        {
+            return Err(self.corrupted());
+        }
+        let mut decryptor = DecryptorBE32::from_aead(
+            cipher(&self.key, &self.name)?,
+            header[ATTACHMENT_MAGIC.len()..].into(),
+        );
+
+        let mut size = 0u64;
+        let mut chunk = vec![0u8; ATTACHMENT_CHUNK_SIZE + TAG_LENGTH];
+        let mut next = vec![0u8; ATTACHMENT_CHUNK_SIZE + TAG_LENGTH];
+        let mut length = read_chunk(&mut reader, &mut chunk)?;
+        loop {
+            let next_length = match length {
+                n if n == chunk.len() => read_chunk(&mut reader, &mut next)?,
+                _ => 0,
+            };
+            if next_length == 0 {
+                let plaintext = Zeroizing::new(
+                    decryptor.decrypt_last(&chunk[..length]).map_err(|_| self.corrupted())?,
+                );
+                writer.write_all(&plaintext)?;
+                size += plaintext.len() as u64;
+                break;
+            }
+            let plaintext = Zeroizing::new(
+                decryptor.decrypt_next(&chunk[..length]).map_err(|_| self.corrupted())?,
+            );
+            writer.write_all(&plaintext)?;
+            size += plaintext.len() as u64;
+            std::mem::swap(&mut chunk, &mut next);
+            length = next_length;
+        }
+        writer.flush()?;
+        if size != self.size {
+            return Err(self.corrupted());
+        }
+        Ok(size)
+    }
+
+    fn corrupted(&self) -> Error {
+        Error::DecryptionError(format!("attachment {:#?} is corrupted or truncated", self.name))
+    }
+}
+
+fn cipher(key: &Secret, name: &str) -> Result<ChaCha20Poly1305> {
+    key.expose(ChaCha20Poly1305::new_from_slice)
+        .map_err(|e| Error::InvalidKeyError(format!("attachment {:#?}: {}", name, e)))
+}
+
+/// Fills `buffer` from `reader`, returning less than `buffer.len()` only
+/// at the end of the input.
+fn read_chunk(reader: &mut impl Read, buffer: &mut [u8]) -> Result<usize> {
+    let mut length = 0;
+    while length < buffer.len() {
+        match reader.read(&mut buffer[length..]) {
+            Ok(0) => break,
+            Ok(n) => length += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(length)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Attachment, Error, Result, ATTACHMENT_CHUNK_SIZE};
+
+    fn roundtrip(plaintext: &[u8]) -> Result<(Attachment, Vec<u8>)> {
+        let mut ciphertext = Vec::new();
+        let attachment = Attachment::encrypt("file.bin", plaintext, &mut ciphertext)?;
+        let mut decrypted = Vec::new();
+        assert_eq!(attachment.decrypt(ciphertext.as_slice(), &mut decrypted)?, attachment.size);
+        assert_eq!(decrypted, plaintext);
+        Ok((attachment, ciphertext))
+    }
+
+    #[test]
+    fn test_attachment_roundtrip() -> Result<()> {
+        for size in [
+            0,
+            1,
+            ATTACHMENT_CHUNK_SIZE,
+            ATTACHMENT_CHUNK_SIZE + 1,
+            3 * ATTACHMENT_CHUNK_SIZE,
+        ] {
+            let plaintext = (0..size).map(|n| n as u8).collect::<Vec<u8>>();
+            let (attachment, ciphertext) = roundtrip(&plaintext)?;
+            assert_eq!(attachment.size, size as u64);
+            assert_eq!(
+                ciphertext.len(),
+                11 + size + 16 * size.div_ceil(ATTACHMENT_CHUNK_SIZE).max(1)
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_attachment_detects_tampering() -> Result<()> {
+        let plaintext = vec![7u8; 2 * ATTACHMENT_CHUNK_SIZE + 10];
+        let (attachment, ciphertext) = roundtrip(&plaintext)?;
+        let chunk = ATTACHMENT_CHUNK_SIZE + 16;
+
+        let mut flipped = ciphertext.clone();
+        flipped[20] ^= 1;
+        let truncated = ciphertext[..11 + 2 * chunk].to_vec();
+        let mut swapped = ciphertext[..11].to_vec();
+        swapped.extend_from_slice(&ciphertext[11 + chunk..11 + 2 * chunk]);
+        swapped.extend_from_slice(&ciphertext[11..11 + chunk]);
+        swapped.extend_from_slice(&ciphertext[11 + 2 * chunk..]);
+        let (other, _) = roundtrip(&plaintext)?;
+
+        for (attachment, ciphertext) in [
+            (&attachment, flipped),
+            (&attachment, truncated),
+            (&attachment, swapped),
+            (&attachment, ciphertext[..5].to_vec()),
+            (&other, ciphertext.clone()),
+        ] {
+            assert!(matches!(
+                attachment.decrypt(ciphertext.as_slice(), std::io::sink()),
+                Err(Error::DecryptionError(_))
+            ));
+        }
+        Ok(())
+    }
+}
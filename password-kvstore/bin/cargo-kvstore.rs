@@ -0,0 +1,5 @@
+use password_kvstore::{Cli, ParserDispatcher};
+
+fn main() {
+    Cli::main()
+}
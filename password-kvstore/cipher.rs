@@ -0,0 +1,157 @@
+use std::marker::PhantomData;
+
+use aes_gcm_siv::Aes256GcmSiv;
+use chacha20poly1305::aead::generic_array::typenum::Unsigned;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, Nonce, OsRng, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, XChaCha20Poly1305};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::{CipherId, Data, DataSeq, DecryptionKey, EncryptionKey, Error, Result, SecretBox};
+
+const BLIND_INDEX_CONTEXT: &[u8] = b"password-kvstore blind index";
+
+/// HMAC-SHA256 of `data` under a sub-key of `key`, so that blind
+/// indexes never reuse the encryption key directly.
+pub(crate) fn blind_index(key: &[u8], data: &[u8]) -> Result<Data> {
+    let index_key = hmac_sha256(key, BLIND_INDEX_CONTEXT)?;
+    hmac_sha256(&index_key.bytes, data)
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Result<Data> {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key)
+        .map_err(|e| Error::InvalidKeyError(format!("HMAC-SHA256 key: {}", e)))?;
+    mac.update(data);
+    Ok(Data::from(mac.finalize().into_bytes().as_slice()))
+}
+
+pub trait AeadAlgorithm: Aead + AeadCore + KeyInit {
+    const CIPHER: CipherId;
+}
+impl AeadAlgorithm for ChaCha20Poly1305 {
+    const CIPHER: CipherId = CipherId::ChaCha20Poly1305;
+}
+impl AeadAlgorithm for XChaCha20Poly1305 {
+    const CIPHER: CipherId = CipherId::XChaCha20Poly1305;
+}
+impl AeadAlgorithm for Aes256GcmSiv {
+    const CIPHER: CipherId = CipherId::Aes256GcmSiv;
+}
+
+pub type ChaCha20Poly1305Key = AeadKey<ChaCha20Poly1305>;
+pub type XChaCha20Poly1305Key = AeadKey<XChaCha20Poly1305>;
+pub type Aes256GcmSivKey = AeadKey<Aes256GcmSiv>;
+
+pub struct AeadKey<A: AeadAlgorithm> {
+    key: SecretBox,
+    algorithm: PhantomData<A>,
+}
+impl<A: AeadAlgorithm> AeadKey<A> {
+    pub fn new(key: &[u8; 32]) -> Result<AeadKey<A>> {
+        Ok(AeadKey {
+            key: SecretBox::close(key)?,
+            algorithm: PhantomData,
+        })
+    }
+
+    fn aead(&self) -> Result<A> {
+        self.key.expose(|key| {
+            A::new_from_slice(key)
+                .map_err(|e| Error::InvalidKeyError(format!("{:?} key: {}", A::CIPHER, e)))
+        })?
+    }
+}
+impl<A: AeadAlgorithm> EncryptionKey for AeadKey<A> {
+    fn cipher(&self) -> CipherId {
+        A::CIPHER
+    }
+
+    fn encrypt_bytes(&self, data: &[u8], aad: &[u8]) -> Result<DataSeq> {
+        let nonce = A::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .aead()?
+            .encrypt(&nonce, Payload { msg: data, aad })
+            .map_err(|e| Error::EncryptionError(format!("{:?}: {}", A::CIPHER, e)))?;
+        let mut seq = DataSeq::new();
+        seq.push(Data::from(nonce.as_slice()));
+        seq.push(Data::new(ciphertext));
+        Ok(seq)
+    }
+
+    fn blind_index(&self, data: &[u8]) -> Result<Data> {
+        self.key.expose(|key| blind_index(key, data))?
+    }
+}
+impl<A: AeadAlgorithm> DecryptionKey for AeadKey<A> {
+    fn decrypt_bytes(&self, data: DataSeq, aad: &[u8]) -> Result<DataSeq> {
+        if data.len() != 2 || data[0].len() != A::NonceSize::to_usize() {
+            return Err(Error::DecryptionError(format!(
+                "malformed {:?} ciphertext",
+                A::CIPHER
+            )));
+        }
+        let payload = Payload {
+            msg: data[1].bytes.as_slice(),
+            aad,
+        };
+        let plaintext = self
+            .aead()?
+            .decrypt(Nonce::<A>::from_slice(&data[0].bytes), payload)
+            .map_err(|_| {
+                Error::DecryptionError(format!(
+                    "{:?} ciphertext failed authentication (wrong key or tampered data)",
+                    A::CIPHER
+                ))
+            })?;
+        let mut seq = DataSeq::new();
+        seq.push(Data::new(plaintext));
+        Ok(seq)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Aes256GcmSivKey, ChaCha20Poly1305Key, Cipher, CipherId, Data, DecryptionKey,
+        EncryptionKey, Error, Result, XChaCha20Poly1305Key,
+    };
+
+    fn roundtrip(cipher: &dyn Cipher, nonce_length: usize) -> Result<()> {
+        let seq = cipher.encrypt_bytes(b"secret", b"name")?;
+        assert_eq!(seq[0].len(), nonce_length);
+        let plaintext = cipher.decrypt_bytes(seq.duplicate(), b"name")?;
+        assert_eq!(plaintext[0], Data::from(b"secret".to_vec()));
+        assert!(matches!(cipher.decrypt_bytes(seq, b"other"), Err(Error::DecryptionError(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_aead_keys() -> Result<()> {
+        let key = [0x42; 32];
+        roundtrip(&ChaCha20Poly1305Key::new(&key)?, 12)?;
+        roundtrip(&XChaCha20Poly1305Key::new(&key)?, 24)?;
+        roundtrip(&Aes256GcmSivKey::new(&key)?, 12)?;
+        assert_eq!(Aes256GcmSivKey::new(&key)?.cipher(), CipherId::Aes256GcmSiv);
+        Ok(())
+    }
+
+    #[test]
+    fn test_blind_index() -> Result<()> {
+        let key = ChaCha20Poly1305Key::new(&[0x42; 32])?;
+        assert_eq!(key.blind_index(b"name")?, key.blind_index(b"name")?);
+        assert_eq!(key.blind_index(b"name")?.len(), 32);
+        assert_ne!(key.blind_index(b"name")?, key.blind_index(b"other")?);
+        let other = ChaCha20Poly1305Key::new(&[0x43; 32])?;
+        assert_ne!(key.blind_index(b"name")?, other.blind_index(b"name")?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_aead_keys_are_not_interchangeable() -> Result<()> {
+        let key = [0x42; 32];
+        let seq = ChaCha20Poly1305Key::new(&key)?.encrypt(b"secret".iter().cloned(), b"")?;
+        let aes = Aes256GcmSivKey::new(&key)?;
+        assert!(matches!(aes.decrypt(seq.iter(), b""), Err(Error::DecryptionError(_))));
+        Ok(())
+    }
+}
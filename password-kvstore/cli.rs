@@ -24,7 +24,9 @@ pub trait ParserDispatcher<E: std::error::Error>: clap::Parser {
     fn args() -> (Vec<String>, bool) {
         let args = iocore::env::args();
         let execname = iocore::Path::new(&args[0]).name();
-        let is_cargo = execname.ends_with("cargo");
+        let subcommand = execname.strip_prefix("cargo-");
+        let is_cargo = execname.ends_with("cargo")
+            || (subcommand.is_some() && args.get(1).map(String::as_str) == subcommand);
         let args = if is_cargo { args[1..].to_vec() } else { args.to_vec() };
         (args, is_cargo)
     }
@@ -0,0 +1,652 @@
+//! The `password-kvstore` executable, see [`Cli`].
+//!
+//! Every command operates on one vault file, given with `--vault` or
+//! `PASSWORD_KVSTORE_VAULT`. Passwords are prompted for on the TTY
+//! unless read from an environment variable (`--password-env VAR`) or
+//! from the first line of an open file descriptor (`--password-fd FD`);
+//! the same holds for entry passwords (`--entry-password-*`) and for the
+//! new master password of `passwd` (`--new-password-*`).
+//!
+//! Installed as `cargo-kvstore` the executable runs as `cargo kvstore`,
+//! see [`ParserDispatcher::dispatch_cargo`], and defaults to a vault in
+//! the current directory rather than in the home directory.
+use std::io::Read;
+use std::time::Duration;
+
+use clap::{Args, Parser, Subcommand};
+use iocore::Path;
+use zeroize::Zeroizing;
+
+use crate::folder::split_parent;
+use crate::{
+    Entry, Error, KdfParams, OtpKind, ParserDispatcher, Result, Secret, Vault,
+    DEFAULT_KDF_MEMORY_KIB, DEFAULT_KDF_TARGET, PATH_SEPARATOR,
+};
+
+/// Vault used when neither `--vault` nor `PASSWORD_KVSTORE_VAULT` is set.
+pub const DEFAULT_VAULT_PATH: &str = "~/.password-kvstore.vault";
+/// Vault used by `cargo kvstore` when neither `--vault` nor
+/// `PASSWORD_KVSTORE_VAULT` is set.
+pub const DEFAULT_CARGO_VAULT_PATH: &str = ".password-kvstore.vault";
+
+#[derive(Parser, Debug, Clone)]
+#[command(version, about = "Stores passwords in an encrypted vault file")]
+pub struct Cli {
+    /// Path of the vault file
+    #[arg(short, long, env = "PASSWORD_KVSTORE_VAULT", global = true)]
+    pub vault: Option<String>,
+
+    #[command(flatten)]
+    pub password: PasswordArgs,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+/// Source of the master password, the TTY by default.
+#[derive(Args, Debug, Clone, Default)]
+pub struct PasswordArgs {
+    /// Reads the master password from the environment variable VAR
+    #[arg(long, value_name = "VAR", global = true, conflicts_with = "password_fd")]
+    pub password_env: Option<String>,
+
+    /// Reads the master password from the first line of the file descriptor FD
+    #[arg(long, value_name = "FD", global = true)]
+    pub password_fd: Option<i32>,
+}
+
+/// Source of an entry password, the TTY by default.
+#[derive(Args, Debug, Clone, Default)]
+pub struct EntryPasswordArgs {
+    /// Reads the entry password from the environment variable VAR
+    #[arg(long, value_name = "VAR", conflicts_with = "entry_password_fd")]
+    pub entry_password_env: Option<String>,
+
+    /// Reads the entry password from the first line of the file descriptor FD
+    #[arg(long, value_name = "FD")]
+    pub entry_password_fd: Option<i32>,
+}
+
+/// Fields of an entry set by `add` and `edit`.
+#[derive(Args, Debug, Clone, Default)]
+pub struct EntryArgs {
+    #[arg(short, long)]
+    pub username: Option<String>,
+
+    #[arg(short, long)]
+    pub email: Option<String>,
+
+    #[arg(short, long)]
+    pub description: Option<String>,
+
+    #[arg(short, long)]
+    pub notes: Option<String>,
+
+    /// Adds a URL, may be repeated
+    #[arg(long = "url", value_name = "URL")]
+    pub urls: Vec<String>,
+
+    /// Adds a tag, may be repeated
+    #[arg(short, long = "tag", value_name = "TAG")]
+    pub tags: Vec<String>,
+
+    #[arg(short, long)]
+    pub favorite: Option<bool>,
+}
+impl EntryArgs {
+    pub fn apply(&self, entry: &mut Entry) {
+        if let Some(username) = &self.username {
+            entry.username = username.to_string();
+        }
+        if let Some(email) = &self.email {
+            entry.email = email.to_string();
+        }
+        if let Some(description) = &self.description {
+            entry.description = description.to_string();
+        }
+        if let Some(notes) = &self.notes {
+            entry.notes = notes.to_string();
+        }
+        for url in &self.urls {
+            if !entry.urls.contains(url) {
+                entry.urls.push(url.to_string());
+            }
+        }
+        entry.tags.extend(self.tags.iter().cloned());
+        if let Some(favorite) = self.favorite {
+            entry.favorite = favorite;
+        }
+    }
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Creates an empty vault
+    Init {
+        /// Milliseconds the master key derivation should take
+        #[arg(long, value_name = "MS", default_value_t = DEFAULT_KDF_TARGET.as_millis() as u64)]
+        kdf_target: u64,
+        /// Memory in KiB the master key derivation may use at most
+        #[arg(long, value_name = "KIB", default_value_t = DEFAULT_KDF_MEMORY_KIB)]
+        kdf_memory: u32,
+    },
+    /// Adds an entry at PATH, as in "infra/aws/root", creating its folders
+    Add {
+        path: String,
+        #[command(flatten)]
+        fields: EntryArgs,
+        #[command(flatten)]
+        password: EntryPasswordArgs,
+    },
+    /// Prints an entry, its password and attribute values hidden unless
+    /// --show is given
+    Get {
+        path: String,
+        /// Prints only FIELD: name, username, email, description, notes,
+        /// urls, tags, password, otp or the name of an attribute
+        #[arg(short, long, value_name = "FIELD")]
+        field: Option<String>,
+        #[arg(short, long)]
+        show: bool,
+    },
+    /// Changes the fields of an entry
+    Edit {
+        path: String,
+        #[command(flatten)]
+        fields: EntryArgs,
+        /// Removes a URL, may be repeated
+        #[arg(long = "remove-url", value_name = "URL")]
+        remove_urls: Vec<String>,
+        /// Removes a tag, may be repeated
+        #[arg(long = "untag", value_name = "TAG")]
+        untags: Vec<String>,
+        /// Replaces the password
+        #[arg(short, long)]
+        password: bool,
+        #[command(flatten)]
+        source: EntryPasswordArgs,
+    },
+    /// Removes an entry, or a folder and everything in it with --recursive
+    Rm {
+        path: String,
+        #[arg(short, long)]
+        recursive: bool,
+    },
+    /// Lists entry paths, of every folder or of FOLDER and its subfolders
+    Ls {
+        folder: Option<String>,
+        /// Also prints usernames and tags
+        #[arg(short, long)]
+        long: bool,
+        /// Lists only entries tagged TAG
+        #[arg(short, long, value_name = "TAG")]
+        tag: Option<String>,
+    },
+    /// Moves the entry at FROM into the folder TO, or renames the folder
+    /// FROM to TO
+    Mv { from: String, to: String },
+    /// Changes the master password
+    Passwd {
+        /// Reads the new master password from the environment variable VAR
+        #[arg(long, value_name = "VAR", conflicts_with = "new_password_fd")]
+        new_password_env: Option<String>,
+        /// Reads the new master password from the first line of the file
+        /// descriptor FD
+        #[arg(long, value_name = "FD")]
+        new_password_fd: Option<i32>,
+    },
+}
+
+impl ParserDispatcher<Error> for Cli {
+    fn dispatch(&self) -> Result<()> {
+        self.execute(DEFAULT_VAULT_PATH)
+    }
+
+    fn dispatch_cargo(&self) -> Result<()> {
+        self.execute(DEFAULT_CARGO_VAULT_PATH)
+    }
+}
+
+impl Cli {
+    /// Runs the command against the vault at `--vault`, or at
+    /// `default_path` when not given.
+    pub fn execute(&self, default_path: &str) -> Result<()> {
+        let path = Path::new(self.vault.as_deref().unwrap_or(default_path)).expand();
+        let is_init = matches!(self.command, Command::Init { .. });
+        let password = read_password(
+            self.password.password_env.as_deref(),
+            self.password.password_fd,
+            "Master password: ",
+            is_init,
+        )?;
+        if let Command::Init {
+            kdf_target,
+            kdf_memory,
+        } = &self.command
+        {
+            let params = KdfParams::calibrate(Duration::from_millis(*kdf_target), *kdf_memory)?;
+            Vault::create_with_params(&path, &password, params)?;
+            return Ok(());
+        }
+        let mut vault = Vault::open(&path, &password)?;
+        match &self.command {
+            Command::Init { .. } => unreachable!(),
+            Command::Add {
+                path,
+                fields,
+                password,
+            } => {
+                let (folder, name) = split_entry_path(path)?;
+                let mut entry = Entry::new(name);
+                fields.apply(&mut entry);
+                entry.password = Secret::from(read_entry_password(password)?.as_str());
+                if vault.folder(folder).is_err() {
+                    vault.add_folder(folder)?;
+                }
+                vault.add_entry(folder, entry)?;
+            },
+            Command::Get { path, field, show } => {
+                let (folder, name) = split_entry_path(path)?;
+                let mut entry = vault.touch_entry(folder, name)?;
+                match field.as_deref() {
+                    Some("otp") => {
+                        println!("{}", entry.otp_code()?);
+                        let otp = entry.otp.as_ref().map(|otp| otp.kind);
+                        if matches!(otp, Some(OtpKind::Hotp { .. })) {
+                            vault.update_entry(folder, entry)?;
+                        }
+                    },
+                    Some(field) => println!("{}", field_value(&entry, field)?.as_str()),
+                    None => print_entry(&entry, *show)?,
+                }
+            },
+            Command::Edit {
+                path,
+                fields,
+                remove_urls,
+                untags,
+                password,
+                source,
+            } => {
+                let (folder, name) = split_entry_path(path)?;
+                let mut entry = vault.get_entry(folder, name)?;
+                fields.apply(&mut entry);
+                entry.urls.retain(|url| !remove_urls.contains(url));
+                entry.url_modes.retain(|url, _| !remove_urls.contains(url));
+                for tag in untags {
+                    entry.tags.remove(tag);
+                }
+                if *password {
+                    entry.password = Secret::from(read_entry_password(source)?.as_str());
+                }
+                vault.update_entry(folder, entry)?;
+            },
+            Command::Rm { path, recursive } =>
+                if *recursive {
+                    vault.remove_folder(path)?;
+                } else {
+                    let (folder, name) = split_entry_path(path)?;
+                    vault.delete_entry(folder, name)?;
+                },
+            Command::Ls { folder, long, tag } => {
+                for line in list(&vault, folder.as_deref(), *long, tag.as_deref())? {
+                    println!("{}", line);
+                }
+                return Ok(());
+            },
+            Command::Mv { from, to } =>
+                if vault.folder(from).is_ok() {
+                    vault.rename_folder(from, to)?;
+                } else {
+                    vault.move_entry(from, to)?;
+                },
+            Command::Passwd {
+                new_password_env,
+                new_password_fd,
+            } => {
+                let password = read_password(
+                    new_password_env.as_deref(),
+                    *new_password_fd,
+                    "New master password: ",
+                    true,
+                )?;
+                vault.change_password(&password)?;
+                return Ok(());
+            },
+        }
+        vault.close()
+    }
+}
+
+/// Lines printed by `ls`.
+fn list(vault: &Vault, folder: Option<&str>, long: bool, tag: Option<&str>) -> Result<Vec<String>> {
+    if let Some(folder) = folder {
+        vault.folder(folder)?;
+    }
+    let mut lines = Vec::new();
+    for path in vault.folder_paths() {
+        let listed = match folder {
+            Some(folder) =>
+                path == folder || path.starts_with(&format!("{}{}", folder, PATH_SEPARATOR)),
+            None => true,
+        };
+        if !listed {
+            continue;
+        }
+        for metadata in vault.metadata(&path)? {
+            if tag.is_some_and(|tag| !metadata.tags.contains(tag)) {
+                continue;
+            }
+            let entry_path = format!("{}{}{}", path, PATH_SEPARATOR, metadata.name);
+            lines.push(match long {
+                true => format!(
+                    "{}\t{}\t{}",
+                    entry_path,
+                    metadata.username,
+                    metadata.tags.iter().cloned().collect::<Vec<String>>().join(",")
+                ),
+                false => entry_path,
+            });
+        }
+    }
+    Ok(lines)
+}
+
+/// Splits an entry path into its folder path and entry name.
+fn split_entry_path(path: &str) -> Result<(&str, &str)> {
+    match split_parent(path)? {
+        (Some(folder), name) => Ok((folder, name)),
+        (None, _) => Err(Error::InvalidFormat(format!(
+            "entry path {:#?} has no folder, as in \"folder{}{}\"",
+            path, PATH_SEPARATOR, path
+        ))),
+    }
+}
+
+fn field_value(entry: &Entry, field: &str) -> Result<Zeroizing<String>> {
+    let value = match field {
+        "name" => entry.name.to_string(),
+        "username" => entry.username.to_string(),
+        "email" => entry.email.to_string(),
+        "description" => entry.description.to_string(),
+        "notes" => entry.notes.to_string(),
+        "urls" => entry.urls.join("\n"),
+        "tags" => entry.tags.iter().cloned().collect::<Vec<String>>().join("\n"),
+        "password" => entry.password.expose_str(str::to_string)?,
+        _ => match entry.attributes.get(field) {
+            Some(value) => value.expose_str(str::to_string)?,
+            None =>
+                return Err(Error::NotFound(format!(
+                    "entry {:#?} has no field {:#?}",
+                    entry.name, field
+                ))),
+        },
+    };
+    Ok(Zeroizing::new(value))
+}
+
+fn print_entry(entry: &Entry, show: bool) -> Result<()> {
+    let hidden = |secret: &Secret| -> Result<Zeroizing<String>> {
+        Ok(Zeroizing::new(match show {
+            true => secret.expose_str(str::to_string)?,
+            false => "********".to_string(),
+        }))
+    };
+    println!("name: {}", entry.name);
+    for (label, value) in [
+        ("username", &entry.username),
+        ("email", &entry.email),
+        ("description", &entry.description),
+    ] {
+        if !value.is_empty() {
+            println!("{}: {}", label, value);
+        }
+    }
+    for url in &entry.urls {
+        println!("url: {} ({})", url, entry.url_mode(url).name());
+    }
+    if !entry.tags.is_empty() {
+        println!("tags: {}", entry.tags.iter().cloned().collect::<Vec<String>>().join(", "));
+    }
+    if entry.favorite {
+        println!("favorite: true");
+    }
+    println!("password: {}", hidden(&entry.password)?.as_str());
+    for (name, value) in &entry.attributes {
+        println!("{}: {}", name, hidden(value)?.as_str());
+    }
+    if !entry.notes.is_empty() {
+        println!("notes:\n{}", entry.notes);
+    }
+    Ok(())
+}
+
+fn read_entry_password(source: &EntryPasswordArgs) -> Result<Zeroizing<String>> {
+    read_password(
+        source.entry_password_env.as_deref(),
+        source.entry_password_fd,
+        "Entry password: ",
+        true,
+    )
+}
+
+/// Reads a password from the environment variable `env`, from the first
+/// line of the file descriptor `fd` or, when neither is given, from a TTY
+/// prompt, asked twice when `confirm` is set.
+fn read_password(
+    env: Option<&str>,
+    fd: Option<i32>,
+    prompt: &str,
+    confirm: bool,
+) -> Result<Zeroizing<String>> {
+    let password = match (env, fd) {
+        (Some(name), _) => Zeroizing::new(std::env::var(name).map_err(|_| {
+            Error::NotFound(format!("environment variable {:#?} is not set", name))
+        })?),
+        (None, Some(fd)) => read_fd(fd)?,
+        (None, None) => {
+            let password = Zeroizing::new(rpassword::prompt_password(prompt)?);
+            if confirm
+                && *password != *Zeroizing::new(rpassword::prompt_password("Retype password: ")?)
+            {
+                return Err(Error::InvalidFormat("passwords do not match".to_string()));
+            }
+            password
+        },
+    };
+    if password.is_empty() {
+        return Err(Error::InvalidFormat("empty password".to_string()));
+    }
+    Ok(password)
+}
+
+/// First line of the file descriptor `fd`, without its line ending. The
+/// descriptor is left open and positioned right after the line, so it is
+/// read one byte at a time.
+fn read_fd(fd: i32) -> Result<Zeroizing<String>> {
+    use std::os::fd::FromRawFd;
+
+    // SAFETY: F_GETFD only reads the flags of `fd`, failing if it is closed.
+    if unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1 {
+        return Err(Error::IOError(format!("invalid file descriptor {}", fd)));
+    }
+    // SAFETY: `fd` is open and stays owned by the caller: the `File` is
+    // wrapped in ManuallyDrop so that it never closes `fd`.
+    let file = std::mem::ManuallyDrop::new(unsafe { std::fs::File::from_raw_fd(fd) });
+    let mut line = Zeroizing::new(Vec::new());
+    let mut byte = Zeroizing::new([0u8; 1]);
+    loop {
+        match (&*file).read(&mut *byte) {
+            Ok(0) => break,
+            Ok(_) if byte[0] == b'\n' => break,
+            Ok(_) => line.push(byte[0]),
+            Err(error) if error.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error.into()),
+        }
+    }
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+    Ok(Zeroizing::new(String::from_utf8(std::mem::take(&mut *line))?))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+    use clap::Parser;
+    use iocore::Path;
+
+    use super::{list, read_fd};
+    use crate::{Cli, Command, Error, KdfAlgorithm, Result, Secret, Vault};
+
+    /// Read end of a pipe holding `lines`, one per line.
+    fn pipe(lines: &[&str]) -> OwnedFd {
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+        let input = lines.iter().map(|line| format!("{}\n", line)).collect::<String>();
+        let written = unsafe { libc::write(fds[1], input.as_ptr().cast(), input.len()) };
+        assert_eq!(written, input.len() as isize);
+        unsafe { libc::close(fds[1]) };
+        unsafe { OwnedFd::from_raw_fd(fds[0]) }
+    }
+
+    fn run(vault: &Path, args: &[&str]) -> Result<()> {
+        let password_pipe = pipe(&["master"]);
+        let password_fd = password_pipe.as_raw_fd().to_string();
+        let mut argv = vec!["password-kvstore", "--vault", vault.path().to_str().unwrap()];
+        argv.extend(["--password-fd", &password_fd]);
+        argv.extend(args);
+        Cli::try_parse_from(argv).unwrap().execute("unused.vault")
+    }
+
+    #[test]
+    fn test_cli_parse() {
+        let cli = Cli::try_parse_from([
+            "password-kvstore",
+            "add",
+            "infra/aws/root",
+            "-u",
+            "admin",
+            "--url",
+            "aws.amazon.com",
+            "-t",
+            "cloud",
+            "-t",
+            "prod",
+            "--password-fd",
+            "3",
+        ])
+        .unwrap();
+        assert_eq!(cli.password.password_fd, Some(3));
+        match cli.command {
+            Command::Add { path, fields, .. } => {
+                assert_eq!(path, "infra/aws/root");
+                assert_eq!(fields.username.as_deref(), Some("admin"));
+                assert_eq!(fields.urls, vec!["aws.amazon.com".to_string()]);
+                assert_eq!(fields.tags, vec!["cloud".to_string(), "prod".to_string()]);
+            },
+            command => panic!("unexpected command {:?}", command),
+        }
+        assert!(Cli::try_parse_from([
+            "password-kvstore",
+            "ls",
+            "--password-env",
+            "A",
+            "--password-fd",
+            "0"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_read_fd() -> Result<()> {
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+        let input = b"master\r\nhunter2\n";
+        assert_eq!(unsafe { libc::write(fds[1], input.as_ptr().cast(), input.len()) }, 16);
+        unsafe { libc::close(fds[1]) };
+        assert_eq!(*read_fd(fds[0])?, "master");
+        assert_eq!(*read_fd(fds[0])?, "hunter2");
+        assert_eq!(*read_fd(fds[0])?, "");
+        unsafe { libc::close(fds[0]) };
+        Ok(())
+    }
+
+    #[test]
+    fn test_cli_commands() -> Result<()> {
+        let path = Path::tmp().join("cli.vault");
+        let entry_pipe = pipe(&["hunter2", "hunter2"]);
+        let new_entry_pipe = pipe(&["hunter3"]);
+        let new_password_pipe = pipe(&["changed"]);
+        let [entry_fd, new_entry_fd, new_password_fd] =
+            [&entry_pipe, &new_entry_pipe, &new_password_pipe].map(|fd| fd.as_raw_fd().to_string());
+        let entry_password = ["--entry-password-fd", entry_fd.as_str()];
+
+        run(&path, &["init", "--kdf-target", "20", "--kdf-memory", "1024"])?;
+        assert!(matches!(
+            run(&path, &["init", "--kdf-memory", "7"]),
+            Err(Error::InvalidKdfParams(_))
+        ));
+        assert!(matches!(
+            run(&path, &["init", "--kdf-target", "20"]),
+            Err(Error::AlreadyExists(_))
+        ));
+        run(
+            &path,
+            &[&["add", "infra/aws/root", "-u", "admin", "-t", "cloud"][..], &entry_password]
+                .concat(),
+        )?;
+        run(&path, &[&["add", "web/github", "-u", "alice"][..], &entry_password].concat())?;
+        assert!(matches!(
+            run(&path, &[&["add", "github"][..], &entry_password].concat()),
+            Err(Error::InvalidFormat(_))
+        ));
+        let accessed_at = Vault::open(&path, "master")?.get_entry_at("infra/aws/root")?.accessed_at;
+        run(&path, &["get", "infra/aws/root", "--field", "password"])?;
+        let vault = Vault::open(&path, "master")?;
+        assert!(vault.get_entry_at("infra/aws/root")?.accessed_at > accessed_at);
+        run(
+            &path,
+            &[
+                "edit",
+                "web/github",
+                "--url",
+                "github.com",
+                "-t",
+                "dev",
+                "--password",
+                "--entry-password-fd",
+                &new_entry_fd,
+            ],
+        )?;
+        run(&path, &["mv", "web/github", "infra"])?;
+        run(&path, &["mv", "infra/aws", "cloud/aws"])?;
+
+        let vault = Vault::open(&path, "master")?;
+        assert_eq!(vault.header().kdf.algorithm, KdfAlgorithm::Argon2id);
+        assert!(vault.header().kdf.argon2_memory_kib <= 1024);
+        assert_eq!(
+            list(&vault, None, false, None)?,
+            vec!["cloud/aws/root".to_string(), "infra/github".to_string()]
+        );
+        assert_eq!(
+            list(&vault, Some("infra"), true, Some("dev"))?,
+            vec!["infra/github\talice\tdev".to_string()]
+        );
+        let entry = vault.get_entry_at("infra/github")?;
+        assert_eq!(entry.password, Secret::from("hunter3"));
+        assert_eq!(entry.urls, vec!["github.com".to_string()]);
+        assert_eq!(entry.history.len(), 1);
+
+        run(&path, &["rm", "infra/github"])?;
+        assert!(matches!(run(&path, &["rm", "infra/github"]), Err(Error::NotFound(_))));
+        run(&path, &["rm", "-r", "cloud"])?;
+        run(&path, &["passwd", "--new-password-fd", &new_password_fd])?;
+        assert!(matches!(Vault::open(&path, "master"), Err(Error::DecryptionError(_))));
+        let vault = Vault::open(&path, "changed")?;
+        assert_eq!(vault.folder_paths(), vec!["infra".to_string(), "web".to_string()]);
+        path.parent().unwrap().delete()?;
+        Ok(())
+    }
+}
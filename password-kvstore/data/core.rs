@@ -1,43 +1,56 @@
 use std::cmp::Ordering;
 use std::collections::BTreeSet;
+use std::hash::{Hash, Hasher};
 use std::iter::{Extend, IntoIterator, Iterator};
 
 use serde::{Deserialize, Serialize};
+use subtle::{Choice, ConstantTimeEq};
 
 use crate::traits::PlainBytes;
 use crate::Result;
 
-#[derive(Clone, PartialOrd, Eq, Ord, Hash, Deserialize, Serialize)]
+#[derive(Eq, Deserialize, Serialize)]
 pub struct Data {
     pub bytes: Vec<u8>,
 }
+/// Compares in constant time with respect to the contents; only the
+/// lengths may short-circuit.
 impl PartialEq for Data {
     fn eq(&self, other: &Self) -> bool {
-        if self.bytes.len() != other.bytes.len() {
-            return false
-        }
-        let mut pos = 0;
-        let len = self.bytes.len();
-        while pos < len {
-            if self.bytes[pos] != other.bytes[pos] {
-                return false
-            }
-            pos += 1;
-        }
-        true
+        self.ct_eq(other).into()
+    }
+}
+impl ConstantTimeEq for Data {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.bytes.as_slice().ct_eq(other.bytes.as_slice())
+    }
+}
+impl Hash for Data {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.bytes.hash(state);
     }
 }
-
 
 impl Data {
     pub fn new(bytes: Vec<u8>) -> Data {
         Data { bytes }
     }
 
+    /// Lends the bytes to `f` without copying them.
+    pub fn expose<T>(&self, f: impl FnOnce(&[u8]) -> T) -> T {
+        f(&self.bytes)
+    }
+
     pub fn to_vec(&self) -> Vec<u8> {
         self.bytes.clone()
     }
 
+    /// Copies the bytes. `Data` does not implement `Clone` so that every
+    /// copy of a plaintext is explicit.
+    pub fn duplicate(&self) -> Data {
+        Data::new(self.to_vec())
+    }
+
     pub fn is_empty(&self) -> bool {
         self.bytes.is_empty()
     }
@@ -124,14 +137,14 @@ impl Data {
     }
 
     pub fn extended<T: Iterator<Item = u8>>(&self, iter: T) -> Data {
-        let mut data = self.clone();
+        let mut data = self.duplicate();
         data.extend(iter);
         data
     }
 
     pub fn then<T>(&self, mut no_more: impl FnMut(Self) -> T) -> Option<T> {
         if self.len() > 0 {
-            Some(no_more(self.clone()))
+            Some(no_more(self.duplicate()))
         } else {
             None
         }
@@ -167,7 +180,7 @@ impl PlainBytes for Data {
     }
 }
 
-#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord, Hash)]
+#[derive(Debug, PartialEq, Eq, Hash)]
 pub struct DataIterator {
     data: Data,
     pos: usize,
@@ -176,7 +189,7 @@ pub struct DataIterator {
 impl DataIterator {
     pub fn new(data: &Data) -> DataIterator {
         DataIterator {
-            data: data.clone(),
+            data: data.duplicate(),
             pos: 0,
         }
     }
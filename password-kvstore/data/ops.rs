@@ -2,14 +2,17 @@ use std::ops::{
     Add, BitXor, BitXorAssign, Div, Drop, Index, IndexMut, Mul, Not, Rem, Shl, Shr, Sub,
 };
 
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
 use super::core::Data;
-use crate::utils::{drop, xor, xor_ip};
+use crate::utils::{xor, xor_ip};
 
 impl Drop for Data {
     fn drop(&mut self) {
-        drop(&mut self.bytes);
+        self.bytes.zeroize();
     }
 }
+impl ZeroizeOnDrop for Data {}
 
 impl BitXor for Data {
     type Output = Self;
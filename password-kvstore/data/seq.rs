@@ -1,17 +1,42 @@
+use std::hash::{Hash, Hasher};
 use std::iter::{Extend, IntoIterator, Iterator};
 use std::ops::Index;
 
 use serde::{Deserialize, Serialize};
+use subtle::{Choice, ConstantTimeEq};
 
 use super::core::Data;
 use crate::traits::PlainBytes;
 use crate::Result;
 
-#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord, Hash, Deserialize, Serialize)]
+#[derive(Debug, Eq, Deserialize, Serialize)]
 pub struct DataSeq {
     seq: Vec<Data>,
     length: usize,
 }
+/// Compares every element in constant time, see [`Data`].
+impl PartialEq for DataSeq {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+impl ConstantTimeEq for DataSeq {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        if self.seq.len() != other.seq.len() {
+            return Choice::from(0);
+        }
+        self.seq
+            .iter()
+            .zip(other.seq.iter())
+            .fold(self.length.ct_eq(&other.length), |equal, (a, b)| equal & a.ct_eq(b))
+    }
+}
+impl Hash for DataSeq {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.seq.hash(state);
+        self.length.hash(state);
+    }
+}
 
 impl DataSeq {
     pub fn new() -> DataSeq {
@@ -22,7 +47,15 @@ impl DataSeq {
     }
 
     pub fn to_vec(&self) -> Vec<Data> {
-        self.seq.clone()
+        self.seq.iter().map(Data::duplicate).collect()
+    }
+
+    /// Copies every chunk, see [`Data::duplicate`].
+    pub fn duplicate(&self) -> DataSeq {
+        DataSeq {
+            seq: self.to_vec(),
+            length: self.length,
+        }
     }
 
     pub fn to_data(&self) -> Result<Data> {
@@ -38,7 +71,7 @@ impl DataSeq {
     }
 
     pub fn get(&mut self, index: usize) -> Option<Data> {
-        self.seq.get(index).map(|byte| byte.clone())
+        self.seq.get(index).map(Data::duplicate)
     }
 
     pub fn push(&mut self, byte: Data) {
@@ -70,7 +103,7 @@ impl DataSeq {
     }
 
     pub fn extended<T: Iterator<Item = Data>>(&self, iter: T) -> DataSeq {
-        let mut data = self.clone();
+        let mut data = self.duplicate();
         data.extend(iter);
         data
     }
@@ -93,7 +126,7 @@ impl From<&Data> for DataSeq {
         DataSeq::from_data(data).expect("data seq bytes")
     }
 }
-#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord, Hash)]
+#[derive(Debug, PartialEq, Eq, Hash)]
 pub struct DataSeqIterator {
     seq: DataSeq,
     pos: usize,
@@ -102,7 +135,7 @@ pub struct DataSeqIterator {
 impl DataSeqIterator {
     pub fn new(seq: &DataSeq) -> DataSeqIterator {
         DataSeqIterator {
-            seq: seq.clone(),
+            seq: seq.duplicate(),
             pos: 0,
         }
     }
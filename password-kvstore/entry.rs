@@ -1,10 +1,20 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::{Duration, SystemTime};
 
+use bincode::Options;
 use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
+use url::Url;
 
-use crate::{PlainBytes, Secret};
+use crate::{
+    Attachment, EntryKind, Error, Otp, PlainBytes, Result, Secret, UrlMatchMode, PATH_SEPARATOR,
+};
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
+/// Number of previous passwords kept in [`Entry::history`].
+pub const PASSWORD_HISTORY_LIMIT: usize = 16;
+
+/// A credential stored in a [`Folder`](crate::Folder).
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct Entry {
     pub name: String,
     pub username: String,
@@ -13,6 +23,95 @@ pub struct Entry {
     pub email: String,
     pub urls: Vec<String>,
     pub attributes: BTreeMap<String, Secret>,
+    pub created_at: SystemTime,
+    pub updated_at: SystemTime,
+    pub accessed_at: SystemTime,
+    pub expires_at: Option<SystemTime>,
+    pub history: Vec<PreviousPassword>,
+    pub otp: Option<Otp>,
+    pub kind: EntryKind,
+    pub attachments: Vec<Attachment>,
+    pub tags: BTreeSet<String>,
+    pub favorite: bool,
+    pub notes: String,
+    pub url_modes: BTreeMap<String, UrlMatchMode>,
+}
+
+/// Non-secret fields of an [`Entry`], see [`Folder::metadata`](crate::Folder::metadata).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct EntryMetadata {
+    pub name: String,
+    pub username: String,
+    pub urls: Vec<String>,
+    pub tags: BTreeSet<String>,
+    pub favorite: bool,
+    pub kind: String,
+    pub created_at: SystemTime,
+    pub updated_at: SystemTime,
+    pub accessed_at: SystemTime,
+    pub expires_at: Option<SystemTime>,
+}
+impl From<&Entry> for EntryMetadata {
+    fn from(entry: &Entry) -> EntryMetadata {
+        EntryMetadata {
+            name: entry.name.to_string(),
+            username: entry.username.to_string(),
+            urls: entry.urls.clone(),
+            tags: entry.tags.clone(),
+            favorite: entry.favorite,
+            kind: entry.kind.name().to_string(),
+            created_at: entry.created_at,
+            updated_at: entry.updated_at,
+            accessed_at: entry.accessed_at,
+            expires_at: entry.expires_at,
+        }
+    }
+}
+impl EntryMetadata {
+    /// See [`Entry::expires_within`].
+    pub fn expires_within(&self, duration: Duration) -> bool {
+        expires_within(self.expires_at, duration)
+    }
+}
+
+fn expires_within(expires_at: Option<SystemTime>, duration: Duration) -> bool {
+    match (expires_at, SystemTime::now().checked_add(duration)) {
+        (Some(expires_at), Some(deadline)) => expires_at <= deadline,
+        (Some(_), None) => true,
+        (None, _) => false,
+    }
+}
+
+/// A password that was replaced at `replaced_at`.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PreviousPassword {
+    pub password: Secret,
+    pub replaced_at: SystemTime,
+}
+impl Default for Entry {
+    fn default() -> Entry {
+        Entry {
+            name: String::new(),
+            username: String::new(),
+            password: Secret::default(),
+            description: String::new(),
+            email: String::new(),
+            urls: Vec::new(),
+            attributes: BTreeMap::new(),
+            created_at: SystemTime::UNIX_EPOCH,
+            updated_at: SystemTime::UNIX_EPOCH,
+            accessed_at: SystemTime::UNIX_EPOCH,
+            expires_at: None,
+            history: Vec::new(),
+            otp: None,
+            kind: EntryKind::Login,
+            attachments: Vec::new(),
+            tags: BTreeSet::new(),
+            favorite: false,
+            notes: String::new(),
+            url_modes: BTreeMap::new(),
+        }
+    }
 }
 impl From<&str> for Entry {
     fn from(name: &str) -> Entry {
@@ -24,18 +123,183 @@ impl From<String> for Entry {
         Entry::new(name.as_str())
     }
 }
-impl PlainBytes for Entry {}
+impl PlainBytes for Entry {
+    fn from_plain_bytes(bytes: &[u8]) -> Result<Entry> {
+        let options = bincode::DefaultOptions::new().with_fixint_encoding().reject_trailing_bytes();
+        options.deserialize::<Entry>(bytes).or_else(|error| {
+            options
+                .deserialize::<EntryV1>(bytes)
+                .map(Entry::from)
+                .map_err(|_| Error::from(error))
+        })
+    }
+
+    fn from_deflate_bytes(bytes: &[u8]) -> Result<Entry> {
+        Entry::from_plain_bytes(&crate::utils::inflate(bytes)?)
+    }
+}
+
+/// Layout of an [`Entry`] before timestamps were recorded.
+#[derive(Deserialize)]
+struct EntryV1 {
+    name: String,
+    username: String,
+    password: Secret,
+    description: String,
+    email: String,
+    urls: Vec<String>,
+    attributes: BTreeMap<String, Secret>,
+}
+impl From<EntryV1> for Entry {
+    fn from(entry: EntryV1) -> Entry {
+        Entry {
+            name: entry.name,
+            username: entry.username,
+            password: entry.password,
+            description: entry.description,
+            email: entry.email,
+            urls: entry.urls,
+            attributes: entry.attributes,
+            ..Default::default()
+        }
+    }
+}
 impl Entry {
     pub fn new(name: &str) -> Entry {
         let mut entry = Entry::default();
         entry.name = name.to_string();
         entry
     }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_within(Duration::ZERO)
+    }
+
+    /// Whether the entry has an expiry that falls within `duration` from
+    /// now, including entries that already expired. Every expiry falls
+    /// within a `duration` too large to represent.
+    pub fn expires_within(&self, duration: Duration) -> bool {
+        expires_within(self.expires_at, duration)
+    }
+
+    /// Validates the name, which must be non-empty and free of NUL bytes
+    /// and [`PATH_SEPARATOR`], the [`kind`](Entry::kind) payload, the
+    /// one-time password settings, the tags, which must not be blank,
+    /// and the URL match modes, which must refer to one of `urls`.
+    pub fn validate(&self) -> Result<()> {
+        if self.name.is_empty() || self.name.contains(['\0', PATH_SEPARATOR]) {
+            return Err(Error::InvalidEntry(format!("invalid entry name {:#?}", self.name)));
+        }
+        self.kind.validate()?;
+        if let Some(otp) = &self.otp {
+            otp.validate()?;
+        }
+        if let Some(tag) = self.tags.iter().find(|tag| tag.trim().is_empty()) {
+            return Err(Error::InvalidEntry(format!(
+                "entry {:#?} has a blank tag {:#?}",
+                self.name, tag
+            )));
+        }
+        for (url, mode) in &self.url_modes {
+            if !self.urls.contains(url) {
+                return Err(Error::InvalidEntry(format!(
+                    "entry {:#?} has no url {:#?} to match by {}",
+                    self.name,
+                    url,
+                    mode.name()
+                )));
+            }
+            if *mode == UrlMatchMode::Regex {
+                crate::url_match::compile(url)
+                    .map_err(|e| Error::InvalidEntry(format!("entry {:#?}: {}", self.name, e)))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn url_mode(&self, url: &str) -> UrlMatchMode {
+        self.url_modes.get(url).copied().unwrap_or_default()
+    }
+
+    /// Adds `url`, matched by `mode`, unless the entry already has it, in
+    /// which case only its mode changes.
+    pub fn set_url(&mut self, url: &str, mode: UrlMatchMode) {
+        if !self.urls.iter().any(|other| other == url) {
+            self.urls.push(url.to_string());
+        }
+        match mode {
+            UrlMatchMode::Domain => self.url_modes.remove(url),
+            mode => self.url_modes.insert(url.to_string(), mode),
+        };
+    }
+
+    /// Best matching URL of the entry for `url` and its score. URLs that
+    /// do not parse never match.
+    pub fn match_url(&self, url: &Url) -> Option<(String, u32)> {
+        self.urls
+            .iter()
+            .filter_map(|pattern| {
+                crate::url_match::score(pattern, self.url_mode(pattern), url)
+                    .ok()
+                    .flatten()
+                    .map(|score| (pattern.to_string(), score))
+            })
+            .max_by_key(|(_, score)| *score)
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.contains(tag)
+    }
+
+    pub fn attachment(&self, name: &str) -> Result<&Attachment> {
+        self.attachments
+            .iter()
+            .find(|attachment| attachment.name == name)
+            .ok_or_else(|| {
+                Error::NotFound(format!("entry {:#?} has no attachment {:#?}", self.name, name))
+            })
+    }
+
+    /// Compares `candidate` with the stored password in constant time.
+    pub fn verify_password(&self, candidate: &str) -> bool {
+        self.password.expose(|password| password.ct_eq(candidate.as_bytes()).into())
+    }
+
+    /// Records `password` as replaced at `replaced_at`, dropping the
+    /// oldest entries beyond [`PASSWORD_HISTORY_LIMIT`].
+    pub fn push_history(&mut self, password: Secret, replaced_at: SystemTime) {
+        self.history.insert(
+            0,
+            PreviousPassword {
+                password,
+                replaced_at,
+            },
+        );
+        self.history.truncate(PASSWORD_HISTORY_LIMIT);
+    }
+
+    /// Sets the one-time password generator from an `otpauth://` URI.
+    pub fn set_otp_uri(&mut self, uri: &str) -> Result<()> {
+        self.otp = Some(Otp::from_uri(uri)?);
+        Ok(())
+    }
+
+    /// Generates the current one-time password, see [`Otp::generate`].
+    /// HOTP entries advance their counter, so the entry has to be stored
+    /// again, e.g. through [`Folder::update_entry`](crate::Folder::update_entry).
+    pub fn otp_code(&mut self) -> Result<String> {
+        match self.otp.as_mut() {
+            Some(otp) => otp.generate(),
+            None => Err(Error::NotFound(format!("entry {:#?} has no otp", self.name))),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{Entry, PlainBytes, Result, Secret};
+    use std::time::SystemTime;
+
+    use crate::{Entry, Error, PlainBytes, Result, Secret, PASSWORD_HISTORY_LIMIT};
     #[test]
     fn test_entry_to_plain_bytes() -> Result<()> {
         let mut entry = Entry::new("entry");
@@ -46,7 +310,10 @@ mod tests {
             vec![
                 5, 0, 0, 0, 0, 0, 0, 0, 101, 110, 116, 114, 121, 0, 0, 0, 0, 0, 0, 0, 0, 5, 0, 0,
                 0, 0, 0, 0, 0, 101, 110, 116, 114, 121, 5, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-                0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0
+                0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+                0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+                0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+                0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0
             ]
         );
         Ok(())
@@ -59,8 +326,8 @@ mod tests {
         assert_eq!(
             entry.to_flate_bytes()?,
             vec![
-                133, 136, 161, 13, 0, 0, 8, 195, 102, 118, 42, 22, 65, 48, 124, 143, 0, 18, 220,
-                170, 218, 18, 131, 121, 70, 173, 131, 127, 94, 40, 26
+                197, 136, 161, 13, 0, 0, 8, 195, 102, 118, 42, 22, 65, 48, 124, 143, 0, 18, 62, 88,
+                85, 91, 98, 48, 207, 168, 117, 240, 207, 11, 1, 13
             ]
         );
         Ok(())
@@ -90,4 +357,42 @@ mod tests {
         assert_eq!(entry_from_bytes, entry);
         Ok(())
     }
+    #[test]
+    fn test_entry_from_plain_bytes_rejects_trailing_bytes() -> Result<()> {
+        let mut bytes = Entry::new("entry").to_plain_bytes();
+        assert_eq!(Entry::from_plain_bytes(&bytes)?, Entry::new("entry"));
+        bytes.push(0);
+        assert!(matches!(Entry::from_plain_bytes(&bytes), Err(Error::IOError(_))));
+        Ok(())
+    }
+    #[test]
+    fn test_entry_push_history() {
+        let mut entry = Entry::new("entry");
+        for n in 0..PASSWORD_HISTORY_LIMIT + 2 {
+            entry.push_history(Secret::from(format!("password-{}", n)), SystemTime::now());
+        }
+        assert_eq!(entry.history.len(), PASSWORD_HISTORY_LIMIT);
+        assert_eq!(
+            entry.history[0].password,
+            Secret::from(format!("password-{}", PASSWORD_HISTORY_LIMIT + 1))
+        );
+        assert_eq!(entry.history[PASSWORD_HISTORY_LIMIT - 1].password, Secret::from("password-2"));
+    }
+    #[test]
+    fn test_entry_verify_password() {
+        let mut entry = Entry::new("entry");
+        entry.password = Secret::from("hunter2");
+        assert!(entry.verify_password("hunter2"));
+        assert!(!entry.verify_password("hunter3"));
+        assert!(!entry.verify_password("hunter"));
+        assert!(!entry.verify_password(""));
+    }
+    #[test]
+    fn test_entry_validate_name() -> Result<()> {
+        Entry::new("aws root").validate()?;
+        for name in ["", "infra/aws", "nul\0byte"] {
+            assert!(matches!(Entry::new(name).validate(), Err(Error::InvalidEntry(_))));
+        }
+        Ok(())
+    }
 }
@@ -25,6 +25,12 @@ pub enum Error {
     StorageError(String),
     PasswordHashingError(String),
     ChaCha20Poly1305Error(String),
+    InvalidFormat(String),
+    UnsupportedVersion(String),
+    InvalidKdfParams(String),
+    MemoryError(String),
+    InvalidOtp(String),
+    InvalidEntry(String),
 }
 
 impl Serialize for Error {
@@ -63,6 +69,12 @@ impl Display for Error {
                 Self::StorageError(e) => e.to_string(),
                 Self::PasswordHashingError(e) => e.to_string(),
                 Self::ChaCha20Poly1305Error(e) => e.to_string(),
+                Self::InvalidFormat(e) => e.to_string(),
+                Self::UnsupportedVersion(e) => e.to_string(),
+                Self::InvalidKdfParams(e) => e.to_string(),
+                Self::MemoryError(e) => e.to_string(),
+                Self::InvalidOtp(e) => e.to_string(),
+                Self::InvalidEntry(e) => e.to_string(),
             }
         )
     }
@@ -88,6 +100,12 @@ impl Error {
             Error::StorageError(_) => "StorageError",
             Error::PasswordHashingError(_) => "PasswordHashingError",
             Error::ChaCha20Poly1305Error(_) => "ChaCha20Poly1305Error",
+            Error::InvalidFormat(_) => "InvalidFormat",
+            Error::UnsupportedVersion(_) => "UnsupportedVersion",
+            Error::InvalidKdfParams(_) => "InvalidKdfParams",
+            Error::MemoryError(_) => "MemoryError",
+            Error::InvalidOtp(_) => "InvalidOtp",
+            Error::InvalidEntry(_) => "InvalidEntry",
         }
         .to_string()
     }
@@ -149,4 +167,9 @@ impl From<chacha20poly1305::Error> for Error {
         Error::ChaCha20Poly1305Error(format!("{}", e))
     }
 }
+impl From<url::ParseError> for Error {
+    fn from(e: url::ParseError) -> Self {
+        Error::InvalidFormat(format!("{}", e))
+    }
+}
 pub type Result<T> = std::result::Result<T, Error>;
@@ -1,79 +1,763 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::{Duration, SystemTime};
 
 use serde::{Deserialize, Serialize};
+use url::Url;
 
-use crate::{Chacha20Tool, CipherText, Entry, Error, PlainBytes, Result, SecretBox};
+use crate::{
+    Attachment, Cipher, Data, DataSeq, Entry, EntryMetadata, Error, OtpKind, PlainBytes,
+    PreviousPassword, Result, SearchMatch, SearchQuery, SecretBox, UrlMatch,
+};
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+/// Separator of the segments of a folder or entry path, as in
+/// `"infra/aws/root"`.
+pub const PATH_SEPARATOR: char = '/';
+
+/// Associated data of the metadata index of a folder, which no entry key
+/// can be equal to.
+const INDEX_AAD: &[u8] = b"\0index";
+
+/// Encrypted entries of a folder, keyed by name or blind index, and its subfolders.
+#[derive(Debug, PartialEq, Eq, Default)]
 pub struct Folder {
     pub name: String,
     pub entries: BTreeMap<String, SecretBox>,
-    pub nonces: BTreeMap<String, [u8; 12]>,
+    pub nonces: BTreeMap<String, Vec<u8>>,
+    pub blind_names: bool,
+    pub folders: BTreeMap<String, Folder>,
+    pub index: BTreeMap<String, EntryMetadata>,
 }
-impl PlainBytes for Folder {}
-impl Folder {
-    fn encrypt_and_insert_entry(&mut self, entry: &Entry, tool: &Chacha20Tool) -> Result<()> {
-        let entry = entry.clone();
-        let entry_ciphertext = tool.encrypt(&entry.to_flate_bytes()?)?;
-        self.entries.insert(
-            entry.name.to_string(),
-            SecretBox::close(&entry_ciphertext.ciphertext().bytes)?,
+
+/// At-rest representation of a [`Folder`]: only the master-key
+/// ciphertext and nonce of every entry and of the metadata index.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub struct StoredFolder {
+    pub name: String,
+    pub entries: BTreeMap<String, Data>,
+    pub nonces: BTreeMap<String, Vec<u8>>,
+    pub blind_names: bool,
+    pub folders: BTreeMap<String, StoredFolder>,
+    pub index: Option<Data>,
+    pub index_nonce: Vec<u8>,
+}
+impl PlainBytes for StoredFolder {}
+
+/// Sealed form of an entry as moved between folders: the key it is
+/// bound to, its wrapped ciphertext, its nonce and its metadata.
+pub(crate) struct SealedEntry {
+    key: String,
+    secret_box: SecretBox,
+    nonce: Vec<u8>,
+    metadata: Option<EntryMetadata>,
+}
+
+/// Splits `path` into its segments, none of which may be empty.
+pub(crate) fn split_path(path: &str) -> Result<Vec<&str>> {
+    let segments = path.split(PATH_SEPARATOR).collect::<Vec<&str>>();
+    if segments.iter().any(|segment| segment.is_empty()) {
+        return Err(Error::InvalidFormat(format!("invalid path {:#?}", path)));
+    }
+    Ok(segments)
+}
+
+/// Splits `path` into its parent path, if any, and its last segment.
+pub(crate) fn split_parent(path: &str) -> Result<(Option<&str>, &str)> {
+    split_path(path)?;
+    Ok(match path.rsplit_once(PATH_SEPARATOR) {
+        Some((parent, name)) => (Some(parent), name),
+        None => (None, path),
+    })
+}
+
+fn folder_not_found(path: &str) -> Error {
+    Error::NotFound(format!("no folder found with name {:#?}", path))
+}
+
+/// Splits `path` into its first segment and the rest, if any.
+fn split_first(path: &str) -> Result<(&str, Option<&str>)> {
+    split_path(path)?;
+    Ok(match path.split_once(PATH_SEPARATOR) {
+        Some((first, rest)) => (first, Some(rest)),
+        None => (path, None),
+    })
+}
+
+/// Folder at `path` below `folders`.
+pub(crate) fn find_folder<'a>(
+    folders: &'a BTreeMap<String, Folder>,
+    path: &str,
+) -> Result<&'a Folder> {
+    let (first, rest) = split_first(path)?;
+    let folder = folders.get(first).ok_or_else(|| folder_not_found(path))?;
+    match rest {
+        Some(rest) => find_folder(&folder.folders, rest).map_err(|_| folder_not_found(path)),
+        None => Ok(folder),
+    }
+}
+
+pub(crate) fn find_folder_mut<'a>(
+    folders: &'a mut BTreeMap<String, Folder>,
+    path: &str,
+) -> Result<&'a mut Folder> {
+    let (first, rest) = split_first(path)?;
+    let folder = folders.get_mut(first).ok_or_else(|| folder_not_found(path))?;
+    match rest {
+        Some(rest) =>
+            find_folder_mut(&mut folder.folders, rest).map_err(|_| folder_not_found(path)),
+        None => Ok(folder),
+    }
+}
+
+/// Folder at `path` below `folders`, created along with any missing
+/// parents.
+pub(crate) fn ensure_folder<'a>(
+    folders: &'a mut BTreeMap<String, Folder>,
+    path: &str,
+) -> Result<&'a mut Folder> {
+    let (first, rest) = split_first(path)?;
+    let folder = folders.entry(first.to_string()).or_insert_with(|| Folder::new(first));
+    match rest {
+        Some(rest) => ensure_folder(&mut folder.folders, rest),
+        None => Ok(folder),
+    }
+}
+
+/// Creates the folder at `path` below `folders` along with any missing
+/// parents.
+pub(crate) fn add_folder<'a>(
+    folders: &'a mut BTreeMap<String, Folder>,
+    path: &str,
+) -> Result<&'a mut Folder> {
+    if find_folder(folders, path).is_ok() {
+        return Err(Error::AlreadyExists(format!("folder {:#?} already exists", path)));
+    }
+    ensure_folder(folders, path)
+}
+
+/// Removes the folder at `path`, subfolders included.
+pub(crate) fn remove_folder(folders: &mut BTreeMap<String, Folder>, path: &str) -> Result<Folder> {
+    let (parent, name) = split_parent(path)?;
+    let siblings = match parent {
+        Some(parent) =>
+            &mut find_folder_mut(folders, parent).map_err(|_| folder_not_found(path))?.folders,
+        None => folders,
+    };
+    siblings.remove(name).ok_or_else(|| folder_not_found(path))
+}
+
+/// Moves the folder at `from` to `to`, creating missing parents of `to`.
+/// Entries are carried over as they are, without re-encryption.
+pub(crate) fn rename_folder(
+    folders: &mut BTreeMap<String, Folder>,
+    from: &str,
+    to: &str,
+) -> Result<()> {
+    let (parent, name) = split_parent(to)?;
+    find_folder(folders, from)?;
+    if to == from || to.starts_with(&format!("{}{}", from, PATH_SEPARATOR)) {
+        return Err(Error::InvalidFormat(format!("cannot move folder {:#?} into {:#?}", from, to)));
+    }
+    if find_folder(folders, to).is_ok() {
+        return Err(Error::AlreadyExists(format!("folder {:#?} already exists", to)));
+    }
+    let mut folder = remove_folder(folders, from)?;
+    folder.name = name.to_string();
+    let siblings = match parent {
+        Some(parent) => &mut ensure_folder(folders, parent)?.folders,
+        None => folders,
+    };
+    siblings.insert(name.to_string(), folder);
+    Ok(())
+}
+
+/// Moves the entry at path `from` into the folder at `to`. The sealed
+/// entry is carried over as it is unless exactly one of the two folders
+/// uses [`blind_names`](Folder::blind_names).
+pub(crate) fn move_entry(
+    folders: &mut BTreeMap<String, Folder>,
+    from: &str,
+    to: &str,
+    tool: &dyn Cipher,
+) -> Result<()> {
+    let (source, name) = match split_parent(from)? {
+        (Some(source), name) => (source, name),
+        (None, _) => {
+            return Err(Error::InvalidFormat(format!("invalid entry path {:#?}", from)));
+        },
+    };
+    if find_folder(folders, to)?.contains(name, tool)? {
+        return Err(Error::AlreadyExists(format!("entry {:#?} already exists in {:#?}", name, to)));
+    }
+    let sealed = find_folder_mut(folders, source)?.take_sealed(name, tool)?;
+    if let Err(error) = find_folder_mut(folders, to)?.insert_sealed(name, &sealed, tool) {
+        find_folder_mut(folders, source)?.insert_sealed(name, &sealed, tool)?;
+        return Err(error);
+    }
+    Ok(())
+}
+
+/// Paths of every folder below `folders`, parents first.
+pub(crate) fn folder_paths(folders: &BTreeMap<String, Folder>) -> Vec<String> {
+    let mut paths = Vec::new();
+    for (name, folder) in folders {
+        paths.push(name.to_string());
+        paths.extend(
+            folder_paths(&folder.folders)
+                .into_iter()
+                .map(|path| format!("{}{}{}", name, PATH_SEPARATOR, path)),
         );
-        self.nonces.insert(entry.name.to_string(), entry_ciphertext.nonce());
+    }
+    paths
+}
+
+fn open_sealed(
+    key: &str,
+    secret_box: &SecretBox,
+    nonce: &[u8],
+    tool: &dyn Cipher,
+) -> Result<Entry> {
+    let mut ciphertext = DataSeq::new();
+    ciphertext.push(Data::new(nonce.to_vec()));
+    ciphertext.push(secret_box.open()?);
+    let bytes = tool.decrypt_bytes(ciphertext, key.as_bytes())?;
+    Entry::from_deflate_bytes(&bytes[0].bytes)
+}
+
+impl Folder {
+    pub fn new(name: &str) -> Folder {
+        Folder {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn to_stored(&self, tool: &dyn Cipher) -> Result<StoredFolder> {
+        let mut entries = BTreeMap::new();
+        for (key, secret_box) in &self.entries {
+            entries.insert(key.to_string(), secret_box.open()?);
+        }
+        let (index, index_nonce) = self.seal_index(tool)?;
+        Ok(StoredFolder {
+            name: self.name.to_string(),
+            entries,
+            nonces: self.nonces.clone(),
+            blind_names: self.blind_names,
+            folders: self
+                .folders
+                .iter()
+                .map(|(name, folder)| Ok((name.to_string(), folder.to_stored(tool)?)))
+                .collect::<Result<_>>()?,
+            index,
+            index_nonce,
+        })
+    }
+
+    pub fn from_stored(folder: StoredFolder, tool: &dyn Cipher) -> Result<Folder> {
+        let mut entries = BTreeMap::new();
+        for (key, ciphertext) in &folder.entries {
+            entries.insert(key.to_string(), SecretBox::close(&ciphertext.bytes)?);
+        }
+        let index = Folder::open_index(folder.index, folder.index_nonce, tool)?;
+        Ok(Folder {
+            name: folder.name,
+            entries,
+            nonces: folder.nonces,
+            blind_names: folder.blind_names,
+            folders: folder
+                .folders
+                .into_iter()
+                .map(|(name, folder)| Ok((name, Folder::from_stored(folder, tool)?)))
+                .collect::<Result<_>>()?,
+            index,
+        })
+    }
+
+    fn key_for(&self, name: &str, tool: &dyn Cipher) -> Result<String> {
+        if self.blind_names {
+            Ok(hex::encode(&tool.blind_index(name.as_bytes())?.bytes))
+        } else {
+            Ok(name.to_string())
+        }
+    }
+
+    fn encrypt_and_insert_entry(&mut self, entry: &Entry, tool: &dyn Cipher) -> Result<()> {
+        self.seal_entry(entry, tool)?;
+        self.index_entries([entry], tool)
+    }
+
+    /// Encrypts and inserts `entry` without updating the index.
+    fn seal_entry(&mut self, entry: &Entry, tool: &dyn Cipher) -> Result<()> {
+        let key = self.key_for(&entry.name, tool)?;
+        let entry_ciphertext = tool.encrypt_bytes(&entry.to_flate_bytes()?, key.as_bytes())?;
+        self.entries
+            .insert(key.to_string(), SecretBox::close(&entry_ciphertext[1].bytes)?);
+        self.nonces.insert(key, entry_ciphertext[0].to_vec());
+        Ok(())
+    }
+
+    fn open_index(
+        index: Option<Data>,
+        nonce: Vec<u8>,
+        tool: &dyn Cipher,
+    ) -> Result<BTreeMap<String, EntryMetadata>> {
+        let index = match index {
+            Some(index) => index,
+            None => return Ok(BTreeMap::new()),
+        };
+        let mut ciphertext = DataSeq::new();
+        ciphertext.push(Data::new(nonce));
+        ciphertext.push(index);
+        let bytes = tool.decrypt_bytes(ciphertext, INDEX_AAD)?;
+        crate::from_deflate_bytes(&bytes[0].bytes)
+    }
+
+    fn seal_index(&self, tool: &dyn Cipher) -> Result<(Option<Data>, Vec<u8>)> {
+        if self.index.is_empty() {
+            return Ok((None, Vec::new()));
+        }
+        let ciphertext = tool.encrypt_bytes(&crate::to_flate_bytes(&self.index)?, INDEX_AAD)?;
+        Ok((Some(Data::new(ciphertext[1].to_vec())), ciphertext[0].to_vec()))
+    }
+
+    fn index_entries<'a>(
+        &mut self,
+        entries: impl IntoIterator<Item = &'a Entry>,
+        tool: &dyn Cipher,
+    ) -> Result<()> {
+        for entry in entries {
+            self.index.insert(self.key_for(&entry.name, tool)?, EntryMetadata::from(entry));
+        }
         Ok(())
     }
 
-    pub fn add_entry(&mut self, entry: Entry, tool: &Chacha20Tool) -> Result<Entry> {
-        let entry = Into::<Entry>::into(entry);
+    /// Metadata of every entry, ordered by name, read from the index.
+    pub fn metadata(&self) -> Vec<EntryMetadata> {
+        let mut metadata = self.index.values().cloned().collect::<Vec<_>>();
+        metadata.sort_by(|a, b| a.name.cmp(&b.name));
+        metadata
+    }
+
+    /// Metadata of the entries for which `predicate` holds, ordered by
+    /// name.
+    pub fn filter_metadata(
+        &self,
+        predicate: impl Fn(&EntryMetadata) -> bool,
+    ) -> Vec<EntryMetadata> {
+        let mut metadata = self.metadata();
+        metadata.retain(|metadata| predicate(metadata));
+        metadata
+    }
+
+    pub fn entry_metadata(&self, name: &str, tool: &dyn Cipher) -> Result<EntryMetadata> {
+        self.index
+            .get(&self.key_for(name, tool)?)
+            .cloned()
+            .ok_or_else(|| Error::NotFound(format!("no entry found with name {:#?}", name)))
+    }
+
+    fn open_entry(&self, key: &str, tool: &dyn Cipher) -> Result<Option<Entry>> {
+        let secret_box = match self.entries.get(key) {
+            Some(ciphertext) => ciphertext,
+            None => return Ok(None),
+        };
+        let nonce = match self.nonces.get(key) {
+            Some(nonce) => nonce,
+            None => return Ok(None),
+        };
+        Ok(Some(open_sealed(key, secret_box, nonce, tool)?))
+    }
+
+    /// Decrypts the entry stored under `key`, which is corrupt when it
+    /// lacks a ciphertext or a nonce.
+    fn open_stored(&self, key: &str, tool: &dyn Cipher) -> Result<Entry> {
+        self.open_entry(key, tool)?.ok_or_else(|| {
+            Error::InvalidFormat(format!("entry {:#?} is missing its ciphertext or nonce", key))
+        })
+    }
+
+    pub fn contains(&self, name: &str, tool: &dyn Cipher) -> Result<bool> {
+        Ok(self.entries.contains_key(&self.key_for(name, tool)?))
+    }
+
+    /// Removes the entry `name` without decrypting it.
+    pub(crate) fn take_sealed(&mut self, name: &str, tool: &dyn Cipher) -> Result<SealedEntry> {
+        let key = self.key_for(name, tool)?;
+        match (self.entries.remove(&key), self.nonces.remove(&key)) {
+            (Some(secret_box), Some(nonce)) => Ok(SealedEntry {
+                metadata: self.index.remove(&key),
+                key,
+                secret_box,
+                nonce,
+            }),
+            _ => Err(Error::NotFound(format!("no entry found with name {:#?}", name))),
+        }
+    }
+
+    /// Inserts an entry taken from another folder with
+    /// [`take_sealed`](Folder::take_sealed), re-encrypting it only when
+    /// this folder keys `name` differently.
+    pub(crate) fn insert_sealed(
+        &mut self,
+        name: &str,
+        sealed: &SealedEntry,
+        tool: &dyn Cipher,
+    ) -> Result<()> {
+        let key = self.key_for(name, tool)?;
+        match (key == sealed.key, &sealed.metadata) {
+            (true, Some(metadata)) => {
+                self.index.insert(key.to_string(), metadata.clone());
+                self.entries.insert(key.to_string(), sealed.secret_box.duplicate());
+                self.nonces.insert(key, sealed.nonce.clone());
+                Ok(())
+            },
+            _ => {
+                let entry = open_sealed(&sealed.key, &sealed.secret_box, &sealed.nonce, tool)?;
+                self.encrypt_and_insert_entry(&entry, tool)
+            },
+        }
+    }
+
+    /// Subfolder at `path`, relative to this folder.
+    pub fn folder(&self, path: &str) -> Result<&Folder> {
+        find_folder(&self.folders, path)
+    }
+
+    pub fn folder_mut(&mut self, path: &str) -> Result<&mut Folder> {
+        find_folder_mut(&mut self.folders, path)
+    }
+
+    /// Creates the subfolder at `path` along with any missing parents.
+    pub fn add_folder(&mut self, path: &str) -> Result<&mut Folder> {
+        add_folder(&mut self.folders, path)
+    }
+
+    pub fn remove_folder(&mut self, path: &str) -> Result<Folder> {
+        remove_folder(&mut self.folders, path)
+    }
+
+    /// Paths of every subfolder, recursively, relative to this folder.
+    pub fn folder_paths(&self) -> Vec<String> {
+        folder_paths(&self.folders)
+    }
+
+    /// Paths of every entry in this folder and its subfolders, relative
+    /// to this folder.
+    pub fn entry_paths(&self) -> Vec<String> {
+        let mut paths = self.names();
+        for (name, folder) in &self.folders {
+            paths.extend(
+                folder
+                    .entry_paths()
+                    .into_iter()
+                    .map(|path| format!("{}{}{}", name, PATH_SEPARATOR, path)),
+            );
+        }
+        paths
+    }
+
+    fn stamp_created(entry: &mut Entry) {
+        let now = SystemTime::now();
+        entry.created_at = now;
+        entry.updated_at = now;
+        entry.accessed_at = now;
+    }
+
+    pub fn add_entry(&mut self, entry: Entry, tool: &dyn Cipher) -> Result<Entry> {
+        let mut entry = Into::<Entry>::into(entry);
+        entry.validate()?;
         let name = entry.name.to_string();
-        if let Some(_) = self.entries.get(&name) {
+        if self.entries.contains_key(&self.key_for(&name, tool)?) {
             return Err(Error::AlreadyExists(format!("entry {:#?} already exists", name)));
         } else {
+            Folder::stamp_created(&mut entry);
             self.encrypt_and_insert_entry(&entry, tool)?;
         }
         Ok(entry)
     }
 
-    pub fn update_entry(&mut self, entry: &Entry, tool: &Chacha20Tool) -> Result<()> {
+    pub fn add_entries(
+        &mut self,
+        entries: impl IntoIterator<Item = Entry>,
+        tool: &dyn Cipher,
+    ) -> Result<Vec<Entry>> {
+        let mut entries = entries.into_iter().collect::<Vec<Entry>>();
+        let mut names = BTreeSet::new();
+        for entry in &entries {
+            entry.validate()?;
+            if self.entries.contains_key(&self.key_for(&entry.name, tool)?)
+                || !names.insert(entry.name.as_str())
+            {
+                return Err(Error::AlreadyExists(format!(
+                    "entry {:#?} already exists",
+                    entry.name
+                )));
+            }
+        }
+        for entry in &mut entries {
+            Folder::stamp_created(entry);
+            self.seal_entry(entry, tool)?;
+        }
+        self.index_entries(&entries, tool)?;
+        Ok(entries)
+    }
+
+    /// Replaces the stored entry of the same name, keeping its
+    /// `created_at`, `accessed_at` and `history` and setting `updated_at`
+    /// to now. A changed password is pushed onto the history. Returns the
+    /// entry as stored.
+    pub fn update_entry(&mut self, mut entry: Entry, tool: &dyn Cipher) -> Result<Entry> {
+        entry.validate()?;
         let name = entry.name.to_string();
-        if self.entries.contains_key(&name) {
-            self.encrypt_and_insert_entry(entry, tool)?;
-            Ok(())
-        } else {
-            Err(Error::NotFound(format!("no entry found with name {:#?}", name)))
+        match self.open_entry(&self.key_for(&name, tool)?, tool)? {
+            Some(stored) => {
+                let now = SystemTime::now();
+                entry.created_at = stored.created_at;
+                entry.accessed_at = stored.accessed_at;
+                entry.updated_at = now;
+                entry.history = stored.history;
+                if entry.password != stored.password {
+                    entry.push_history(stored.password, now);
+                }
+                self.encrypt_and_insert_entry(&entry, tool)?;
+                Ok(entry)
+            },
+            None => Err(Error::NotFound(format!("no entry found with name {:#?}", name))),
         }
     }
 
-    pub fn get_nonce(&self, name: &str) -> Result<[u8; 12]> {
-        let name = name.to_string();
-        match self.nonces.get(&name) {
+    /// Previous passwords of the entry, most recent first.
+    pub fn history(&self, name: &str, tool: &dyn Cipher) -> Result<Vec<PreviousPassword>> {
+        match self.open_entry(&self.key_for(name, tool)?, tool)? {
+            Some(entry) => Ok(entry.history),
+            None => Err(Error::NotFound(format!("no entry found with name {:#?}", name))),
+        }
+    }
+
+    /// Makes `history[index]` the current password again. The password it
+    /// replaces is pushed onto the history, so a restore can be undone.
+    pub fn restore_password(
+        &mut self,
+        name: &str,
+        index: usize,
+        tool: &dyn Cipher,
+    ) -> Result<Entry> {
+        let mut entry = match self.open_entry(&self.key_for(name, tool)?, tool)? {
+            Some(entry) => entry,
+            None => return Err(Error::NotFound(format!("no entry found with name {:#?}", name))),
+        };
+        if index >= entry.history.len() {
+            return Err(Error::NotFound(format!(
+                "entry {:#?} has no previous password at index {}",
+                name, index
+            )));
+        }
+        let now = SystemTime::now();
+        let restored = entry.history.remove(index);
+        let replaced = std::mem::replace(&mut entry.password, restored.password);
+        entry.push_history(replaced, now);
+        entry.updated_at = now;
+        self.encrypt_and_insert_entry(&entry, tool)?;
+        Ok(entry)
+    }
+
+    /// Generates the entry's current one-time password. HOTP entries
+    /// are stored again through [`update_entry`](Folder::update_entry) so
+    /// the advanced counter is never reused.
+    pub fn otp_code(&mut self, name: &str, tool: &dyn Cipher) -> Result<String> {
+        let mut entry = match self.open_entry(&self.key_for(name, tool)?, tool)? {
+            Some(entry) => entry,
+            None => return Err(Error::NotFound(format!("no entry found with name {:#?}", name))),
+        };
+        let code = entry.otp_code()?;
+        if matches!(entry.otp.as_ref().map(|otp| otp.kind), Some(OtpKind::Hotp { .. })) {
+            self.update_entry(entry, tool)?;
+        }
+        Ok(code)
+    }
+
+    pub fn get_nonce(&self, name: &str, tool: &dyn Cipher) -> Result<Vec<u8>> {
+        match self.nonces.get(&self.key_for(name, tool)?) {
             Some(nonce) => Ok(nonce.clone()),
             None => Err(Error::NotFound(format!("no entry (nonce) found with name {:#?}", name))),
         }
     }
 
-    pub fn get(&self, name: &str, tool: &Chacha20Tool) -> Result<Entry> {
-        let name = name.to_string();
-        let secret_box = match self.entries.get(&name) {
-            Some(ciphertext) => ciphertext,
-            None => {
-                return Err(Error::NotFound(format!("no entry found with name {:#?}", name)));
-            },
-        };
-        let nonce = self.get_nonce(&name)?;
+    pub fn get(&self, name: &str, tool: &dyn Cipher) -> Result<Entry> {
+        match self.open_entry(&self.key_for(name, tool)?, tool)? {
+            Some(entry) => Ok(entry),
+            None => Err(Error::NotFound(format!("no entry found with name {:#?}", name))),
+        }
+    }
 
-        let entry_ciphertext = secret_box.open()?;
-        let ciphertext = CipherText::new(entry_ciphertext.clone(), nonce);
-        let bytes = tool.decrypt(&ciphertext)?;
-        let entry = Entry::from_deflate_bytes(&bytes)?;
+    /// Records an access to the entry in its `accessed_at`.
+    pub fn touch(&mut self, name: &str, tool: &dyn Cipher) -> Result<Entry> {
+        let mut entry = self.get(name, tool)?;
+        entry.accessed_at = SystemTime::now();
+        self.encrypt_and_insert_entry(&entry, tool)?;
         Ok(entry)
     }
 
-    pub fn delete(&mut self, name: &str) -> Result<bool> {
-        let name = name.to_string();
-        let entry_deleted = self.entries.remove(&name).map(|_| true).unwrap_or_else(|| false);
+    /// Entries whose `expires_at` falls within `duration` from now,
+    /// already expired ones included, soonest first.
+    pub fn expiring_within(&self, duration: Duration, tool: &dyn Cipher) -> Result<Vec<Entry>> {
+        let mut entries = Vec::new();
+        for (key, metadata) in &self.index {
+            if metadata.expires_within(duration) {
+                entries.push(self.open_stored(key, tool)?);
+            }
+        }
+        entries.sort_by_key(|entry| entry.expires_at);
+        Ok(entries)
+    }
+
+    /// Attachments of every entry in this folder and its subfolders.
+    pub fn attachments(&self, tool: &dyn Cipher) -> Result<Vec<Attachment>> {
+        let mut attachments = Vec::new();
+        for key in self.entries.keys() {
+            attachments.extend(self.open_stored(key, tool)?.attachments);
+        }
+        for folder in self.folders.values() {
+            attachments.extend(folder.attachments(tool)?);
+        }
+        Ok(attachments)
+    }
+
+    /// Entries matching `query`, best match first, see
+    /// [`SearchQuery`] for the syntax. Secret values are not searched.
+    pub fn search(&self, query: &str, tool: &dyn Cipher) -> Result<Vec<SearchMatch>> {
+        self.search_with(&SearchQuery::parse(query)?, tool)
+    }
+
+    /// Decrypts every entry once and ranks those matching `query`.
+    pub fn search_with(&self, query: &SearchQuery, tool: &dyn Cipher) -> Result<Vec<SearchMatch>> {
+        query.validate()?;
+        let mut matches = Vec::new();
+        for key in self.entries.keys() {
+            let entry = self.open_stored(key, tool)?;
+            if let Some(score) = query.score(&entry) {
+                matches.push(SearchMatch { entry, score });
+            }
+        }
+        matches.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.entry.name.cmp(&b.entry.name)));
+        Ok(matches)
+    }
+
+    /// Entries with a URL matching `url`, best match first, see
+    /// [`UrlMatchMode`](crate::UrlMatchMode) for how each entry URL is
+    /// compared.
+    pub fn match_url(&self, url: &Url, tool: &dyn Cipher) -> Result<Vec<UrlMatch>> {
+        let mut matches = Vec::new();
+        for key in self.entries.keys() {
+            let entry = self.open_stored(key, tool)?;
+            if let Some((url, score)) = entry.match_url(url) {
+                matches.push(UrlMatch { entry, url, score });
+            }
+        }
+        matches.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.entry.name.cmp(&b.entry.name)));
+        Ok(matches)
+    }
+
+    /// Entries tagged with `tag`, ordered by name. Only the matching
+    /// entries are decrypted.
+    pub fn find_by_tag(&self, tag: &str, tool: &dyn Cipher) -> Result<Vec<Entry>> {
+        let mut entries = Vec::new();
+        for metadata in self.filter_metadata(|metadata| metadata.tags.contains(tag)) {
+            entries.push(self.open_stored(&self.key_for(&metadata.name, tool)?, tool)?);
+        }
+        Ok(entries)
+    }
+
+    /// Every tag in use along with the number of entries carrying it.
+    pub fn list_tags(&self) -> BTreeMap<String, usize> {
+        let mut tags = BTreeMap::new();
+        for metadata in self.metadata() {
+            for tag in metadata.tags {
+                *tags.entry(tag).or_insert(0) += 1;
+            }
+        }
+        tags
+    }
+
+    /// Replaces tag `from` with `to` on every entry carrying it,
+    /// returning the number of entries updated.
+    pub fn rename_tag(&mut self, from: &str, to: &str, tool: &dyn Cipher) -> Result<usize> {
+        if to.trim().is_empty() {
+            return Err(Error::InvalidEntry(format!("blank tag {:#?}", to)));
+        }
+        let entries = self.find_by_tag(from, tool)?;
+        let count = entries.len();
+        for mut entry in entries {
+            entry.tags.remove(from);
+            entry.tags.insert(to.to_string());
+            self.update_entry(entry, tool)?;
+        }
+        Ok(count)
+    }
+
+    /// Plaintext names of every entry, read from the index when
+    /// [`blind_names`](Folder::blind_names) is enabled.
+    pub fn names(&self) -> Vec<String> {
+        if !self.blind_names {
+            return self.entries.keys().cloned().collect();
+        }
+        self.metadata().into_iter().map(|metadata| metadata.name).collect()
+    }
+
+    /// Switches between plaintext and blind-indexed entry names,
+    /// re-encrypting every entry under its new key.
+    pub fn set_blind_names(&mut self, enabled: bool, tool: &dyn Cipher) -> Result<()> {
+        if self.blind_names == enabled {
+            return Ok(());
+        }
+        let mut folder = Folder {
+            name: self.name.to_string(),
+            blind_names: enabled,
+            ..Default::default()
+        };
+        let mut entries = Vec::new();
+        for key in self.entries.keys() {
+            let entry = self.open_stored(key, tool)?;
+            folder.seal_entry(&entry, tool)?;
+            entries.push(entry);
+        }
+        folder.index_entries(&entries, tool)?;
+        folder.folders = std::mem::take(&mut self.folders);
+        *self = folder;
+        Ok(())
+    }
+
+    pub fn rekey(&mut self, old: &dyn Cipher, new: &dyn Cipher) -> Result<()> {
+        *self = self.rekeyed(old, new)?;
+        Ok(())
+    }
+
+    /// Copy of this folder and its subfolders with every entry encrypted
+    /// under `new` instead of `old`.
+    pub(crate) fn rekeyed(&self, old: &dyn Cipher, new: &dyn Cipher) -> Result<Folder> {
+        let mut rekeyed = Folder {
+            name: self.name.to_string(),
+            blind_names: self.blind_names,
+            ..Default::default()
+        };
+        let mut entries = Vec::new();
+        for key in self.entries.keys() {
+            let entry = self.open_stored(key, old)?;
+            rekeyed.seal_entry(&entry, new)?;
+            entries.push(entry);
+        }
+        rekeyed.index_entries(&entries, new)?;
+        for (name, folder) in &self.folders {
+            rekeyed.folders.insert(name.to_string(), folder.rekeyed(old, new)?);
+        }
+        Ok(rekeyed)
+    }
+
+    pub fn delete(&mut self, name: &str, tool: &dyn Cipher) -> Result<bool> {
+        let key = self.key_for(name, tool)?;
+        let entry_deleted = self.entries.remove(&key).map(|_| true).unwrap_or_else(|| false);
         if entry_deleted {
-            self.nonces.remove(&name);
+            self.nonces.remove(&key);
+            self.index.remove(&key);
             Ok(true)
         } else {
             Err(Error::NotFound(format!("no entry found with name {:#?}", name)))
@@ -82,20 +766,483 @@ impl Folder {
 }
 #[cfg(test)]
 mod tests {
-    use crate::{Chacha20Tool, Entry, Error, Folder, Result, Secret};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use crate::{
+        Chacha20Tool, CipherId, Entry, EntryKind, Error, Folder, KdfParams, PlainBytes, Result,
+        SearchMatch, SearchQuery, Secret, StoredFolder, UrlMatchMode,
+    };
     #[test]
     fn test_folder() -> Result<()> {
         let tool = Chacha20Tool::new("password", 600)?;
         let mut folder = Folder::default();
         let mut entry = folder.add_entry(Entry::from("entry"), &tool)?;
         entry.password = Secret::from("entry");
-        folder.update_entry(&entry, &tool)?;
+        let entry = folder.update_entry(entry, &tool)?;
         assert_eq!(folder.get("entry", &tool)?, entry);
-        folder.delete(entry.name.as_str())?;
+        folder.delete(entry.name.as_str(), &tool)?;
         assert_eq!(
             folder.get("entry", &tool),
             Err(Error::NotFound(format!("no entry found with name {:#?}", "entry")))
         );
         Ok(())
     }
+
+    #[test]
+    fn test_folder_add_entries() -> Result<()> {
+        let tool = Chacha20Tool::new("password", 600)?.unlock()?;
+        let mut folder = Folder::default();
+        let entries = (0..2000).map(|n| Entry::from(format!("entry-{}", n)));
+        let entries = folder.add_entries(entries, &tool)?;
+        assert_eq!(entries.len(), 2000);
+        assert_eq!(folder.get("entry-1999", &tool)?, entries[1999]);
+        assert!(matches!(
+            folder.add_entries(vec![Entry::from("new"), Entry::from("entry-0")], &tool),
+            Err(Error::AlreadyExists(_))
+        ));
+        assert!(matches!(folder.get("new", &tool), Err(Error::NotFound(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_folder_ciphers() -> Result<()> {
+        for cipher in
+            [CipherId::ChaCha20Poly1305, CipherId::XChaCha20Poly1305, CipherId::Aes256GcmSiv]
+        {
+            let tool = Chacha20Tool::builder()
+                .params(KdfParams::with_iterations(600))
+                .cipher(cipher)
+                .build("password")?;
+            let mut folder = Folder::default();
+            let entry = folder.add_entry(Entry::from("entry"), &tool)?;
+            assert_eq!(folder.get("entry", &tool)?, entry);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_folder_rekey() -> Result<()> {
+        let old = Chacha20Tool::new("password", 600)?;
+        let new = Chacha20Tool::new("new password", 600)?;
+        let mut folder = Folder::default();
+        let entry = folder.add_entry(Entry::from("entry"), &old)?;
+        folder.rekey(&old, &new)?;
+        assert_eq!(folder.get("entry", &new)?, entry);
+        assert!(matches!(folder.get("entry", &old), Err(Error::DecryptionError(_))));
+
+        let before = folder.to_stored(&new)?.entries;
+        assert!(matches!(folder.rekey(&old, &new), Err(Error::DecryptionError(_))));
+        assert_eq!(folder.to_stored(&new)?.entries, before);
+        Ok(())
+    }
+
+    #[test]
+    fn test_folder_detects_swapped_entries() -> Result<()> {
+        let tool = Chacha20Tool::new("password", 600)?;
+        let mut folder = Folder::default();
+        folder.add_entry(Entry::from("first"), &tool)?;
+        folder.add_entry(Entry::from("second"), &tool)?;
+
+        let first = folder.entries.remove("first").unwrap();
+        let second = folder.entries.remove("second").unwrap();
+        let first_nonce = folder.get_nonce("first", &tool)?;
+        let second_nonce = folder.get_nonce("second", &tool)?;
+        folder.entries.insert("first".to_string(), second);
+        folder.entries.insert("second".to_string(), first);
+        folder.nonces.insert("first".to_string(), second_nonce);
+        folder.nonces.insert("second".to_string(), first_nonce);
+
+        assert!(matches!(folder.get("first", &tool), Err(Error::DecryptionError(_))));
+        assert!(matches!(folder.get("second", &tool), Err(Error::DecryptionError(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_folder_rejects_missing_nonce() -> Result<()> {
+        let tool = Chacha20Tool::new("password", 600)?;
+        let mut folder = Folder::default();
+        folder.add_entry(Entry::from("entry"), &tool)?;
+        folder.add_folder("sub")?;
+        folder.nonces.remove("entry");
+
+        assert!(matches!(folder.set_blind_names(true, &tool), Err(Error::InvalidFormat(_))));
+        assert!(matches!(folder.rekey(&tool, &tool), Err(Error::InvalidFormat(_))));
+        assert!(matches!(folder.search("entry", &tool), Err(Error::InvalidFormat(_))));
+        assert!(!folder.blind_names);
+        assert_eq!(folder.folder_paths(), vec!["sub"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_folder_blind_names() -> Result<()> {
+        let tool = Chacha20Tool::new("password", 600)?;
+        let mut folder = Folder::default();
+        folder.add_entry(Entry::from("aws-prod-root"), &tool)?;
+        folder.set_blind_names(true, &tool)?;
+        let mut entry = folder.add_entry(Entry::from("payroll-bank"), &tool)?;
+
+        let bytes = folder.to_stored(&tool)?.to_plain_bytes();
+        for name in ["aws-prod-root", "payroll-bank"] {
+            assert!(!bytes.windows(name.len()).any(|window| window == name.as_bytes()));
+        }
+        assert_eq!(folder.names(), vec!["aws-prod-root", "payroll-bank"]);
+        assert!(matches!(
+            folder.add_entry(Entry::from("payroll-bank"), &tool),
+            Err(Error::AlreadyExists(_))
+        ));
+
+        entry.password = Secret::from("hunter2");
+        let entry = folder.update_entry(entry, &tool)?;
+        assert_eq!(folder.get("payroll-bank", &tool)?, entry);
+        folder.delete("aws-prod-root", &tool)?;
+        assert!(matches!(folder.get("aws-prod-root", &tool), Err(Error::NotFound(_))));
+
+        let new = Chacha20Tool::new("new password", 600)?;
+        folder.rekey(&tool, &new)?;
+        assert!(folder.blind_names);
+        assert_eq!(folder.get("payroll-bank", &new)?, entry);
+
+        folder.set_blind_names(false, &new)?;
+        assert_eq!(folder.entries.keys().collect::<Vec<_>>(), vec!["payroll-bank"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_folder_stored_roundtrip() -> Result<()> {
+        let tool = Chacha20Tool::new("password", 600)?;
+        let mut folder = Folder::default();
+        let entry = folder.add_entry(Entry::from("entry"), &tool)?;
+        let stored = folder.to_stored(&tool)?;
+        let secret_box = folder.entries.get("entry").unwrap();
+        let bytes = stored.to_plain_bytes();
+        let wrapped = &secret_box.ciphertext.bytes;
+        assert!(!bytes.windows(wrapped.len()).any(|window| window == wrapped));
+
+        let folder = Folder::from_stored(StoredFolder::from_plain_bytes(&bytes)?, &tool)?;
+        assert_ne!(folder.entries.get("entry").unwrap(), secret_box);
+        assert_eq!(folder.get("entry", &tool)?, entry);
+        Ok(())
+    }
+
+    #[test]
+    fn test_folder_timestamps() -> Result<()> {
+        let tool = Chacha20Tool::new("password", 600)?;
+        let mut folder = Folder::default();
+        let mut entry = folder.add_entry(Entry::from("entry"), &tool)?;
+        assert!(entry.created_at > UNIX_EPOCH);
+        assert_eq!(entry.updated_at, entry.created_at);
+
+        entry.created_at = UNIX_EPOCH;
+        let (updated_at, accessed_at) = (entry.updated_at, entry.accessed_at);
+        let updated = folder.update_entry(entry, &tool)?;
+        assert_ne!(updated.created_at, UNIX_EPOCH);
+        assert!(updated.updated_at >= updated_at);
+        assert_eq!(updated.accessed_at, accessed_at);
+        assert_eq!(folder.get("entry", &tool)?, updated);
+
+        let touched = folder.touch("entry", &tool)?;
+        assert!(touched.accessed_at >= updated.updated_at);
+        assert_eq!(touched.updated_at, updated.updated_at);
+        assert_eq!(folder.get("entry", &tool)?, touched);
+
+        let day = Duration::from_secs(86400);
+        let mut expired = Entry::from("expired");
+        expired.expires_at = Some(SystemTime::now() - day);
+        let mut soon = Entry::from("soon");
+        soon.expires_at = Some(SystemTime::now() + day);
+        let mut later = Entry::from("later");
+        later.expires_at = Some(SystemTime::now() + 30 * day);
+        folder.add_entries(vec![later, soon, expired], &tool)?;
+
+        let names = |entries: Vec<Entry>| entries.into_iter().map(|e| e.name).collect::<Vec<_>>();
+        assert_eq!(names(folder.expiring_within(Duration::ZERO, &tool)?), vec!["expired"]);
+        assert_eq!(names(folder.expiring_within(7 * day, &tool)?), vec!["expired", "soon"]);
+        assert_eq!(names(folder.expiring_within(Duration::MAX, &tool)?).len(), 3);
+        assert!(folder.get("expired", &tool)?.is_expired());
+        folder.nonces.remove("later");
+        assert_eq!(names(folder.expiring_within(7 * day, &tool)?), vec!["expired", "soon"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_folder_password_history() -> Result<()> {
+        let tool = Chacha20Tool::new("password", 600)?;
+        let mut folder = Folder::default();
+        let mut entry = Entry::from("entry");
+        entry.password = Secret::from("first");
+        let mut entry = folder.add_entry(entry, &tool)?;
+        assert!(folder.history("entry", &tool)?.is_empty());
+
+        entry.description = "unchanged password".to_string();
+        let mut entry = folder.update_entry(entry, &tool)?;
+        assert!(folder.history("entry", &tool)?.is_empty());
+
+        entry.password = Secret::from("second");
+        let mut entry = folder.update_entry(entry, &tool)?;
+        entry.password = Secret::from("third");
+        folder.update_entry(entry, &tool)?;
+        let history = folder.history("entry", &tool)?;
+        assert_eq!(
+            history.iter().map(|previous| &previous.password).collect::<Vec<_>>(),
+            vec![&Secret::from("second"), &Secret::from("first")]
+        );
+
+        let restored = folder.restore_password("entry", 1, &tool)?;
+        assert_eq!(restored.password, Secret::from("first"));
+        assert_eq!(folder.get("entry", &tool)?.password, Secret::from("first"));
+        assert_eq!(
+            folder
+                .history("entry", &tool)?
+                .into_iter()
+                .map(|previous| previous.password)
+                .collect::<Vec<_>>(),
+            vec![Secret::from("third"), Secret::from("second")]
+        );
+        assert!(matches!(folder.restore_password("entry", 2, &tool), Err(Error::NotFound(_))));
+        assert!(matches!(folder.history("missing", &tool), Err(Error::NotFound(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_folder_otp_code() -> Result<()> {
+        let tool = Chacha20Tool::new("password", 600)?;
+        let mut folder = Folder::default();
+        let mut entry = Entry::from("entry");
+        entry.set_otp_uri(
+            "otpauth://hotp/entry?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&counter=0",
+        )?;
+        folder.add_entry(entry, &tool)?;
+        folder.add_entry(Entry::from("plain"), &tool)?;
+
+        assert_eq!(folder.otp_code("entry", &tool)?, "755224");
+        assert_eq!(folder.otp_code("entry", &tool)?, "287082");
+        let mut entry = folder.get("entry", &tool)?;
+        assert_eq!(entry.otp_code()?, "359152");
+        folder.update_entry(entry, &tool)?;
+        assert_eq!(folder.otp_code("entry", &tool)?, "969429");
+        assert!(matches!(folder.otp_code("plain", &tool), Err(Error::NotFound(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_folder_validates_entry_kind() -> Result<()> {
+        let tool = Chacha20Tool::new("password", 600)?;
+        let mut folder = Folder::default();
+        let mut entry = Entry::from("card");
+        entry.kind = EntryKind::Card {
+            cardholder: "J DOE".to_string(),
+            number: Secret::from("4111 1111 1111 1111"),
+            expiry_month: 12,
+            expiry_year: 2030,
+            cvv: Secret::from("123"),
+        };
+        let entry = folder.add_entry(entry, &tool)?;
+        assert_eq!(folder.get("card", &tool)?.kind.name(), "card");
+
+        let invalid = |mut entry: Entry| {
+            if let EntryKind::Card { number, .. } = &mut entry.kind {
+                *number = Secret::from("4111 1111 1111 1112");
+            }
+            entry
+        };
+        assert!(matches!(
+            folder.update_entry(invalid(entry), &tool),
+            Err(Error::InvalidEntry(_))
+        ));
+        let mut entry = invalid(folder.get("card", &tool)?);
+        entry.name = "other".to_string();
+        assert!(matches!(folder.add_entry(entry, &tool), Err(Error::InvalidEntry(_))));
+        assert_eq!(folder.names(), vec!["card"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_folder_subfolders() -> Result<()> {
+        let old = Chacha20Tool::new("password", 600)?;
+        let new = Chacha20Tool::new("new password", 600)?;
+        let mut folder = Folder::new("team");
+        folder.add_entry(Entry::from("vpn"), &old)?;
+        folder.add_folder("infra/aws")?.add_entry(Entry::from("root"), &old)?;
+        assert_eq!(folder.folder_paths(), vec!["infra", "infra/aws"]);
+        assert_eq!(folder.entry_paths(), vec!["vpn", "infra/aws/root"]);
+
+        folder.set_blind_names(true, &old)?;
+        folder.rekey(&old, &new)?;
+        assert_eq!(folder.folder_mut("infra/aws")?.get("root", &new)?.name, "root");
+        let stored = folder.to_stored(&new)?;
+        let restored =
+            Folder::from_stored(StoredFolder::from_plain_bytes(&stored.to_plain_bytes())?, &new)?;
+        assert_eq!(restored.to_stored(&new)?.entries, stored.entries);
+        assert_eq!(restored.index, folder.index);
+
+        let sealed = folder.folder_mut("infra/aws")?.take_sealed("root", &new)?;
+        folder.insert_sealed("root", &sealed, &new)?;
+        assert_eq!(folder.get("root", &new)?.name, "root");
+        assert!(folder.contains("root", &new)?);
+        assert!(!folder.folder("infra/aws")?.contains("root", &new)?);
+        assert!(matches!(folder.remove_folder("infra/gcp"), Err(Error::NotFound(_))));
+        folder.remove_folder("infra")?;
+        assert!(folder.folder_paths().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_folder_tags() -> Result<()> {
+        let tool = Chacha20Tool::new("password", 600)?;
+        let mut folder = Folder::default();
+        for (name, tags) in [
+            ("db", vec!["prod", "team-data"]),
+            ("api", vec!["prod"]),
+            ("staging-db", vec!["staging", "team-data"]),
+            ("scratch", vec![]),
+        ] {
+            let mut entry = Entry::new(name);
+            entry.tags = tags.into_iter().map(String::from).collect();
+            entry.favorite = name == "db";
+            entry.notes = "rotated by\nthe on-call".to_string();
+            folder.add_entry(entry, &tool)?;
+        }
+        let names = |entries: Vec<Entry>| entries.into_iter().map(|e| e.name).collect::<Vec<_>>();
+        assert_eq!(names(folder.find_by_tag("prod", &tool)?), vec!["api", "db"]);
+        assert!(folder.find_by_tag("qa", &tool)?.is_empty());
+        assert_eq!(
+            folder.list_tags().into_iter().collect::<Vec<_>>(),
+            vec![
+                ("prod".to_string(), 2),
+                ("staging".to_string(), 1),
+                ("team-data".to_string(), 2)
+            ]
+        );
+
+        assert_eq!(folder.rename_tag("prod", "production", &tool)?, 2);
+        assert!(folder.find_by_tag("prod", &tool)?.is_empty());
+        let db = folder.get("db", &tool)?;
+        assert!(db.has_tag("production") && db.has_tag("team-data") && db.favorite);
+        assert_eq!(db.notes, "rotated by\nthe on-call");
+        assert!(matches!(folder.rename_tag("staging", " ", &tool), Err(Error::InvalidEntry(_))));
+
+        let mut entry = Entry::new("blank");
+        entry.tags.insert(String::new());
+        assert!(matches!(folder.add_entry(entry, &tool), Err(Error::InvalidEntry(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_folder_search() -> Result<()> {
+        let tool = Chacha20Tool::new("password", 600)?;
+        let mut folder = Folder::default();
+        for (name, username, url) in [
+            ("github", "alice", "https://github.com"),
+            ("github-bot", "ci", "https://github.com"),
+            ("gitlab", "alice", "https://gitlab.com"),
+            ("bank", "alice", "https://bank.example"),
+        ] {
+            let mut entry = Entry::new(name);
+            entry.username = username.to_string();
+            entry.urls = vec![url.to_string()];
+            entry.password = Secret::from("github");
+            folder.add_entry(entry, &tool)?;
+        }
+        let names = |matches: Vec<SearchMatch>| {
+            matches.into_iter().map(|m| m.entry.name).collect::<Vec<_>>()
+        };
+        assert_eq!(names(folder.search("github", &tool)?), vec!["github", "github-bot"]);
+        assert_eq!(names(folder.search("user:alice url:git", &tool)?), vec!["github", "gitlab"]);
+        assert_eq!(names(folder.search("gtlb", &tool)?), vec!["gitlab"]);
+        assert_eq!(names(folder.search("", &tool)?).len(), 4);
+        assert_eq!(
+            names(folder.search_with(&SearchQuery::parse("github")?.with_secrets(), &tool)?),
+            vec!["github", "github-bot", "bank", "gitlab"]
+        );
+        assert!(matches!(folder.search("password:github", &tool), Err(Error::InvalidFormat(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_folder_match_url() -> Result<()> {
+        let tool = Chacha20Tool::new("password", 600)?;
+        let mut folder = Folder::default();
+        for (name, url, mode) in [
+            ("example", "example.com", UrlMatchMode::Domain),
+            ("example-login", "https://login.example.com", UrlMatchMode::Origin),
+            ("example-admin", "https://login.example.com/admin/", UrlMatchMode::Prefix),
+            ("example-any", r"https://.*\.example\.com/.*", UrlMatchMode::Regex),
+            ("other", "example.org", UrlMatchMode::Domain),
+        ] {
+            let mut entry = Entry::new(name);
+            entry.set_url(url, mode);
+            folder.add_entry(entry, &tool)?;
+        }
+        let names = |url: &str| -> Result<Vec<(String, u32)>> {
+            Ok(folder
+                .match_url(&url::Url::parse(url).unwrap(), &tool)?
+                .into_iter()
+                .map(|m| (m.entry.name, m.score))
+                .collect())
+        };
+        assert_eq!(
+            names("https://login.example.com/admin/users")?,
+            vec![
+                ("example-admin".to_string(), 80),
+                ("example-login".to_string(), 60),
+                ("example-any".to_string(), 50),
+                ("example".to_string(), 20),
+            ]
+        );
+        assert_eq!(names("http://login.example.com/")?, vec![("example".to_string(), 20)]);
+        assert!(names("https://example.net")?.is_empty());
+
+        let mut entry = Entry::new("invalid");
+        entry.set_url("(", UrlMatchMode::Regex);
+        assert!(matches!(folder.add_entry(entry, &tool), Err(Error::InvalidEntry(_))));
+        let mut entry = Entry::new("orphan");
+        entry.url_modes.insert("example.com".to_string(), UrlMatchMode::Host);
+        assert!(matches!(folder.add_entry(entry, &tool), Err(Error::InvalidEntry(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_folder_metadata() -> Result<()> {
+        let tool = Chacha20Tool::new("password", 600)?;
+        let mut folder = Folder::default();
+        assert!(folder.metadata().is_empty());
+        let mut entries = Vec::new();
+        for (name, username, tag) in [("db", "postgres", "prod"), ("api", "svc", "staging")] {
+            let mut entry = Entry::new(name);
+            entry.username = username.to_string();
+            entry.password = Secret::from("hunter2");
+            entry.urls = vec![format!("https://{}.example.com", name)];
+            entry.tags.insert(tag.to_string());
+            entries.push(entry);
+        }
+        folder.add_entries(entries, &tool)?;
+        let mut entry = folder.add_entry(Entry::from("cache"), &tool)?;
+
+        let metadata = folder.metadata();
+        assert_eq!(
+            metadata.iter().map(|m| m.name.as_str()).collect::<Vec<_>>(),
+            vec!["api", "cache", "db"]
+        );
+        assert_eq!(metadata[2].username, "postgres");
+        assert_eq!(metadata[2].urls, vec!["https://db.example.com"]);
+        assert_eq!(metadata[2].kind, "login");
+        assert_eq!(folder.filter_metadata(|m| m.tags.contains("prod"))[0].name, "db");
+
+        entry.username = "redis".to_string();
+        folder.update_entry(entry, &tool)?;
+        assert_eq!(folder.entry_metadata("cache", &tool)?.username, "redis");
+        folder.delete("api", &tool)?;
+        assert!(matches!(folder.entry_metadata("api", &tool), Err(Error::NotFound(_))));
+
+        folder.set_blind_names(true, &tool)?;
+        let new = Chacha20Tool::new("new password", 600)?;
+        folder.rekey(&tool, &new)?;
+        let folder = Folder::from_stored(folder.to_stored(&new)?, &new)?;
+        assert_eq!(folder.names(), vec!["cache", "db"]);
+        assert_eq!(folder.entry_metadata("db", &new)?.username, "postgres");
+
+        let accessed_at = folder.get("db", &new)?.accessed_at;
+        assert_eq!(folder.entry_metadata("db", &new)?.accessed_at, accessed_at);
+        Ok(())
+    }
 }
@@ -0,0 +1,106 @@
+//! On-disk layout of a [`Vault`](crate::Vault) file:
+//!
+//! | offset    | length | content                                              |
+//! |-----------|--------|------------------------------------------------------|
+//! | `0`       | `8`    | [`MAGIC`]                                            |
+//! | `8`       | `2`    | format version, little-endian `u16`                  |
+//! | `10`      | `4`    | header length `n`, little-endian `u32`               |
+//! | `14`      | `n`    | bincode-encoded [`Header`]                           |
+//! | `14 + n`  | ..     | bincode-encoded [`DataSeq`](crate::DataSeq)          |
+use serde::{Deserialize, Serialize};
+
+use crate::{Data, Error, KdfParams, PlainBytes, Result};
+
+pub const MAGIC: [u8; 8] = *b"PWKVSTOR";
+pub const FORMAT_VERSION: u16 = 1;
+pub const SALT_LENGTH: usize = 32;
+const PREFIX_LENGTH: usize = 14;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum CipherId {
+    #[default]
+    ChaCha20Poly1305,
+    XChaCha20Poly1305,
+    Aes256GcmSiv,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Header {
+    #[serde(skip)]
+    pub version: u16,
+    pub cipher: CipherId,
+    pub kdf: KdfParams,
+    pub salt: Vec<u8>,
+}
+impl PlainBytes for Header {}
+impl Header {
+    pub fn new(kdf: KdfParams) -> Header {
+        Header {
+            version: FORMAT_VERSION,
+            cipher: CipherId::default(),
+            kdf,
+            salt: Data::randomc(rand::rng(), SALT_LENGTH).to_vec(),
+        }
+    }
+
+    pub fn encode(&self, payload: &[u8]) -> Result<Vec<u8>> {
+        let header = bincode::serialize(self)?;
+        let mut bytes = Vec::with_capacity(PREFIX_LENGTH + header.len() + payload.len());
+        bytes.extend_from_slice(&MAGIC);
+        bytes.extend_from_slice(&self.version.to_le_bytes());
+        bytes.extend_from_slice(&(header.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&header);
+        bytes.extend_from_slice(payload);
+        Ok(bytes)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<(Header, &[u8])> {
+        if bytes.len() < PREFIX_LENGTH || bytes[..8] != MAGIC {
+            return Err(Error::InvalidFormat("not a vault file (missing magic bytes)".to_string()));
+        }
+        let version = u16::from_le_bytes([bytes[8], bytes[9]]);
+        if version != FORMAT_VERSION {
+            return Err(Error::UnsupportedVersion(format!(
+                "vault format version {} is not supported (expected {})",
+                version, FORMAT_VERSION
+            )));
+        }
+        let length = u32::from_le_bytes([bytes[10], bytes[11], bytes[12], bytes[13]]) as usize;
+        if bytes.len() < PREFIX_LENGTH + length {
+            return Err(Error::InvalidFormat("truncated vault header".to_string()));
+        }
+        let mut header =
+            bincode::deserialize::<Header>(&bytes[PREFIX_LENGTH..PREFIX_LENGTH + length])
+                .map_err(|e| Error::InvalidFormat(format!("invalid vault header: {}", e)))?;
+        header.version = version;
+        Ok((header, &bytes[PREFIX_LENGTH + length..]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Error, Header, KdfParams, Result, FORMAT_VERSION, MAGIC};
+
+    #[test]
+    fn test_header_encode_decode() -> Result<()> {
+        let header = Header::new(KdfParams::default());
+        let bytes = header.encode(b"payload")?;
+        assert_eq!(bytes[..8], MAGIC);
+        assert_eq!(bytes[8..10], FORMAT_VERSION.to_le_bytes());
+        let (decoded, payload) = Header::decode(&bytes)?;
+        assert_eq!(decoded, header);
+        assert_eq!(payload, b"payload");
+        Ok(())
+    }
+
+    #[test]
+    fn test_header_rejects_unknown_version() -> Result<()> {
+        let mut bytes = Header::new(KdfParams::default()).encode(b"payload")?;
+        bytes[8..10].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        assert!(matches!(Header::decode(&bytes), Err(Error::UnsupportedVersion(_))));
+        bytes[8..10].copy_from_slice(&0u16.to_le_bytes());
+        assert!(matches!(Header::decode(&bytes), Err(Error::UnsupportedVersion(_))));
+        assert!(matches!(Header::decode(b"payload"), Err(Error::InvalidFormat(_))));
+        Ok(())
+    }
+}
@@ -0,0 +1,221 @@
+use std::time::{Duration, Instant};
+
+use argon2_kdf::{Algorithm, Hasher};
+use pbkdf2::pbkdf2_hmac;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::{Error, Result};
+
+const CALIBRATION_PASSWORD: &[u8] = b"password-kvstore calibration";
+const CALIBRATION_SALT: &[u8] = b"password-kvstore calibration salt";
+const CALIBRATION_PBKDF2_ROUNDS: u32 = 10000;
+
+/// Unlock time targeted when calibrating the KDF of a new vault.
+pub const DEFAULT_KDF_TARGET: Duration = Duration::from_secs(1);
+/// Memory cap, in KiB, when calibrating the KDF of a new vault.
+pub const DEFAULT_KDF_MEMORY_KIB: u32 = 262144;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum KdfAlgorithm {
+    Pbkdf2Sha256,
+    Argon2id,
+    Argon2i,
+}
+
+/// Parameters of the master key derivation.
+///
+/// `iterations` is the PBKDF2 round count; the `argon2_*` fields
+/// configure the Argon2 algorithms. `argon2_salt_length` and
+/// `argon2_hash_length` size the hash returned by
+/// [`Chacha20Tool::hash`](crate::Chacha20Tool::hash); the master key
+/// itself is always 32 bytes long.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct KdfParams {
+    pub algorithm: KdfAlgorithm,
+    pub iterations: u32,
+    pub argon2_memory_kib: u32,
+    pub argon2_iterations: u32,
+    pub argon2_threads: u32,
+    pub argon2_salt_length: u32,
+    pub argon2_hash_length: u32,
+}
+impl Default for KdfParams {
+    fn default() -> KdfParams {
+        KdfParams {
+            algorithm: KdfAlgorithm::Argon2id,
+            iterations: crate::DEFAULT_ITERATIONS,
+            argon2_memory_kib: 65536,
+            argon2_iterations: 3,
+            argon2_threads: 4,
+            argon2_salt_length: 12,
+            argon2_hash_length: 42,
+        }
+    }
+}
+impl KdfParams {
+    /// PBKDF2-SHA256 with `iterations` rounds.
+    pub fn with_iterations(iterations: u32) -> KdfParams {
+        KdfParams {
+            algorithm: KdfAlgorithm::Pbkdf2Sha256,
+            iterations,
+            ..Default::default()
+        }
+    }
+
+    pub fn derive(&self, password: &[u8], salt: &[u8]) -> Result<[u8; 32]> {
+        let mut key = [0; 32];
+        match self.algorithm {
+            KdfAlgorithm::Pbkdf2Sha256 => {
+                pbkdf2_hmac::<Sha256>(password, salt, self.iterations, &mut key);
+            },
+            KdfAlgorithm::Argon2id | KdfAlgorithm::Argon2i => {
+                let algorithm = match self.algorithm {
+                    KdfAlgorithm::Argon2i => Algorithm::Argon2i,
+                    _ => Algorithm::Argon2id,
+                };
+                let hash = Hasher::new()
+                    .algorithm(algorithm)
+                    .custom_salt(salt)
+                    .hash_length(key.len() as u32)
+                    .iterations(self.argon2_iterations)
+                    .memory_cost_kib(self.argon2_memory_kib)
+                    .threads(self.argon2_threads)
+                    .hash(password)?;
+                key.copy_from_slice(hash.as_bytes());
+            },
+        }
+        Ok(key)
+    }
+
+    /// Benchmarks PBKDF2-SHA256 and Argon2id on the current machine and
+    /// returns Argon2id parameters whose derivation takes roughly
+    /// `target`, using at most `max_memory_kib` of memory. The PBKDF2
+    /// `iterations` are calibrated to the same target so that switching
+    /// [`algorithm`](KdfParams::algorithm) keeps the unlock time.
+    /// Fails when `max_memory_kib` is below Argon2's minimum of 8 KiB.
+    pub fn calibrate(target: Duration, max_memory_kib: u32) -> Result<KdfParams> {
+        if max_memory_kib < 8 {
+            return Err(Error::InvalidKdfParams(format!(
+                "argon2 memory must be at least 8 KiB, got {} KiB",
+                max_memory_kib
+            )));
+        }
+        let threads = std::thread::available_parallelism()
+            .map(|threads| threads.get() as u32)
+            .unwrap_or(1)
+            .min(4)
+            .min(max_memory_kib / 8);
+        let mut params = KdfParams {
+            algorithm: KdfAlgorithm::Argon2id,
+            argon2_threads: threads,
+            argon2_iterations: 1,
+            argon2_memory_kib: max_memory_kib,
+            ..Default::default()
+        };
+        params.iterations = KdfParams::calibrate_pbkdf2(target)?;
+
+        let mut elapsed = params.time_derive()?;
+        while elapsed > target && params.argon2_memory_kib / 2 >= 8 * threads {
+            params.argon2_memory_kib /= 2;
+            elapsed = params.time_derive()?;
+        }
+        let per_iteration = elapsed.as_secs_f64().max(f64::EPSILON);
+        params.argon2_iterations = ((target.as_secs_f64() / per_iteration) as u32).max(1);
+        params.validate()?;
+        Ok(params)
+    }
+
+    fn calibrate_pbkdf2(target: Duration) -> Result<u32> {
+        let params = KdfParams {
+            algorithm: KdfAlgorithm::Pbkdf2Sha256,
+            iterations: CALIBRATION_PBKDF2_ROUNDS,
+            ..Default::default()
+        };
+        let per_round = params.time_derive()?.as_secs_f64() / CALIBRATION_PBKDF2_ROUNDS as f64;
+        let rounds = target.as_secs_f64() / per_round.max(f64::EPSILON);
+        Ok(rounds.min(u32::MAX as f64).max(1.0) as u32)
+    }
+
+    fn time_derive(&self) -> Result<Duration> {
+        let start = Instant::now();
+        self.derive(CALIBRATION_PASSWORD, CALIBRATION_SALT)?;
+        Ok(start.elapsed())
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.iterations == 0 {
+            return Err(Error::InvalidKdfParams("iterations must be at least 1".to_string()));
+        }
+        if self.argon2_iterations == 0 {
+            return Err(Error::InvalidKdfParams(
+                "argon2 iterations must be at least 1".to_string(),
+            ));
+        }
+        if self.argon2_threads == 0 {
+            return Err(Error::InvalidKdfParams("argon2 threads must be at least 1".to_string()));
+        }
+        if self.argon2_memory_kib < 8 * self.argon2_threads {
+            return Err(Error::InvalidKdfParams(format!(
+                "argon2 memory must be at least {} KiB for {} threads",
+                8 * self.argon2_threads,
+                self.argon2_threads
+            )));
+        }
+        if self.argon2_salt_length < 8 {
+            return Err(Error::InvalidKdfParams(
+                "argon2 salt length must be at least 8 bytes".to_string(),
+            ));
+        }
+        if self.argon2_hash_length < 4 {
+            return Err(Error::InvalidKdfParams(
+                "argon2 hash length must be at least 4 bytes".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{Error, KdfAlgorithm, KdfParams, Result};
+
+    #[test]
+    fn test_kdf_params_validate() -> Result<()> {
+        let params = KdfParams::default();
+        params.validate()?;
+        assert_eq!(params.algorithm, KdfAlgorithm::Argon2id);
+        assert_eq!(KdfParams::with_iterations(600).algorithm, KdfAlgorithm::Pbkdf2Sha256);
+        assert!(matches!(
+            KdfParams::with_iterations(0).validate(),
+            Err(Error::InvalidKdfParams(_))
+        ));
+        let params = KdfParams {
+            argon2_memory_kib: 8,
+            argon2_threads: 2,
+            ..Default::default()
+        };
+        assert!(matches!(params.validate(), Err(Error::InvalidKdfParams(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_kdf_params_calibrate() -> Result<()> {
+        let params = KdfParams::calibrate(Duration::from_millis(20), 1024)?;
+        params.validate()?;
+        assert_eq!(params.algorithm, KdfAlgorithm::Argon2id);
+        assert!(params.argon2_memory_kib <= 1024);
+        assert!(params.argon2_iterations >= 1);
+        assert!(params.iterations >= 1);
+
+        let params = KdfParams::calibrate(Duration::from_millis(20), 8)?;
+        assert_eq!((params.argon2_memory_kib, params.argon2_threads), (8, 1));
+        assert!(matches!(
+            KdfParams::calibrate(Duration::from_millis(20), 7),
+            Err(Error::InvalidKdfParams(_))
+        ));
+        Ok(())
+    }
+}
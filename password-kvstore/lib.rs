@@ -1,25 +1,51 @@
+pub(crate) mod attachment;
+pub(crate) mod cipher;
+pub(crate) mod cli;
+pub(crate) mod command;
 pub(crate) mod data;
 pub(crate) mod entry;
 pub(crate) mod errors;
 pub(crate) mod folder;
+pub(crate) mod header;
+pub(crate) mod kdf;
+pub(crate) mod kind;
+pub(crate) mod memory;
+pub(crate) mod otp;
+pub(crate) mod search;
 pub(crate) mod secret;
 pub(crate) mod secret_box;
 pub(crate) mod traits;
 pub(crate) mod utils;
 pub(crate) mod tool;
+pub(crate) mod url_match;
+pub(crate) mod vault;
 
+pub use attachment::{Attachment, ATTACHMENT_CHUNK_SIZE, ATTACHMENT_MAGIC};
+pub use cipher::{
+    AeadAlgorithm, AeadKey, Aes256GcmSivKey, ChaCha20Poly1305Key, XChaCha20Poly1305Key,
+};
+pub use cli::ParserDispatcher;
+pub use command::{Cli, Command, EntryArgs, EntryPasswordArgs, PasswordArgs};
 pub use data::{Data, DataSeq, DataSeqIterator};
-pub use entry::Entry;
+pub use entry::{Entry, EntryMetadata, PreviousPassword, PASSWORD_HISTORY_LIMIT};
 pub use errors::{Error, Result};
-pub use folder::Folder;
+pub use folder::{Folder, StoredFolder, PATH_SEPARATOR};
+pub use header::{CipherId, Header, FORMAT_VERSION, MAGIC, SALT_LENGTH};
+pub use kdf::{KdfAlgorithm, KdfParams, DEFAULT_KDF_MEMORY_KIB, DEFAULT_KDF_TARGET};
+pub use kind::EntryKind;
+pub use memory::{harden_process, LockedBytes};
+pub use otp::{Otp, OtpAlgorithm, OtpKind, DEFAULT_OTP_DIGITS, DEFAULT_OTP_PERIOD};
+pub use search::{SearchField, SearchMatch, SearchQuery, SearchTerm, FUZZY_MIN_LENGTH};
 // pub use password::{password_decrypt_bytes, password_encrypt_bytes};
 pub use secret::Secret;
 pub use secret_box::SecretBox;
-pub use traits::PlainBytes;
+pub use traits::{Cipher, DecryptionKey, EncryptionKey, PlainBytes};
 pub use utils::{
     chunk_padded, discharge, drop, from_deflate_bytes, rev, scrub_with_byte, to_flate_bytes, xor,
     xor_ip, zerofill,
 };
 pub use tool::{
-    Chacha20Tool, CipherText
+    Chacha20Tool, Chacha20ToolBuilder, CipherText, DEFAULT_ITERATIONS
 };
+pub use url_match::{parse_entry_url, registrable_domain, UrlMatch, UrlMatchMode};
+pub use vault::Vault;
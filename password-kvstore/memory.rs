@@ -0,0 +1,281 @@
+//! Hardened storage for unlocked key material.
+//!
+//! [`LockedBytes`] keeps its bytes on dedicated pages that are locked
+//! into RAM with `mlock`, excluded from core dumps with
+//! `MADV_DONTDUMP` and surrounded by inaccessible guard pages, so that
+//! keys are neither swapped out nor written to core files, and buffer
+//! overruns into or out of them fault instead of leaking.
+//! [`harden_process`] additionally disables core dumps and `ptrace`
+//! attach for the whole process.
+use subtle::{Choice, ConstantTimeEq};
+use zeroize::Zeroize;
+
+use crate::Result;
+
+/// Fixed-length buffer for key material, see the [module
+/// documentation](self).
+///
+/// Locking is best effort: when `RLIMIT_MEMLOCK` is exhausted the pages
+/// stay pageable and [`is_locked`](LockedBytes::is_locked) returns
+/// `false`. The bytes are wiped before the pages are released.
+pub struct LockedBytes {
+    region: sys::Region,
+    len: usize,
+}
+impl LockedBytes {
+    /// Allocates `len` zeroed bytes.
+    pub fn new(len: usize) -> Result<LockedBytes> {
+        Ok(LockedBytes {
+            region: sys::Region::new(len)?,
+            len,
+        })
+    }
+
+    pub fn from_slice(bytes: &[u8]) -> Result<LockedBytes> {
+        let mut locked = LockedBytes::new(bytes.len())?;
+        locked.expose_mut(|buffer| buffer.copy_from_slice(bytes));
+        Ok(locked)
+    }
+
+    pub fn expose<T>(&self, f: impl FnOnce(&[u8]) -> T) -> T {
+        f(&self.region.as_slice()[..self.len])
+    }
+
+    pub fn expose_mut<T>(&mut self, f: impl FnOnce(&mut [u8]) -> T) -> T {
+        f(&mut self.region.as_mut_slice()[..self.len])
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.region.is_locked()
+    }
+
+    /// Copies the bytes into a new region, failing instead of panicking
+    /// when no more memory can be mapped.
+    pub fn try_clone(&self) -> Result<LockedBytes> {
+        self.expose(LockedBytes::from_slice)
+    }
+}
+impl Drop for LockedBytes {
+    fn drop(&mut self) {
+        self.region.as_mut_slice().zeroize();
+    }
+}
+impl PartialEq for LockedBytes {
+    fn eq(&self, other: &LockedBytes) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+impl ConstantTimeEq for LockedBytes {
+    fn ct_eq(&self, other: &LockedBytes) -> Choice {
+        self.expose(|bytes| other.expose(|other| bytes.ct_eq(other)))
+    }
+}
+impl Eq for LockedBytes {}
+impl std::fmt::Debug for LockedBytes {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "LockedBytes({} bytes)", self.len)
+    }
+}
+
+/// Disables core dumps for the current process and, on Linux, marks it
+/// non-dumpable, which also refuses `ptrace` attach from processes that
+/// are not privileged. Meant to be called once, early in `main`.
+pub fn harden_process() -> Result<()> {
+    sys::harden_process()
+}
+
+#[cfg(unix)]
+mod sys {
+    use std::ptr::NonNull;
+
+    use crate::{Error, Result};
+
+    pub struct Region {
+        base: NonNull<u8>,
+        size: usize,
+        page_size: usize,
+        capacity: usize,
+        locked: bool,
+    }
+    // SAFETY: the region is exclusively owned and only reachable through
+    // `&self`/`&mut self`.
+    unsafe impl Send for Region {}
+    unsafe impl Sync for Region {}
+
+    impl Region {
+        pub fn new(len: usize) -> Result<Region> {
+            let page_size = page_size()?;
+            let capacity = len.max(1).div_ceil(page_size) * page_size;
+            let size = capacity + 2 * page_size;
+            // SAFETY: anonymous private mapping, checked for MAP_FAILED.
+            let base = unsafe {
+                libc::mmap(
+                    std::ptr::null_mut(),
+                    size,
+                    libc::PROT_NONE,
+                    libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                    -1,
+                    0,
+                )
+            };
+            if std::ptr::eq(base, libc::MAP_FAILED) {
+                return Err(last_error("mmap"));
+            }
+            let mut region = Region {
+                base: NonNull::new(base.cast()).ok_or_else(|| last_error("mmap"))?,
+                size,
+                page_size,
+                capacity,
+                locked: false,
+            };
+            let data = region.data().cast();
+            // SAFETY: `data..data + capacity` lies within the mapping; the
+            // first and last page keep PROT_NONE as guard pages.
+            unsafe {
+                if libc::mprotect(data, capacity, libc::PROT_READ | libc::PROT_WRITE) != 0 {
+                    return Err(last_error("mprotect"));
+                }
+                #[cfg(any(target_os = "linux", target_os = "android"))]
+                libc::madvise(data, capacity, libc::MADV_DONTDUMP);
+                region.locked = libc::mlock(data, capacity) == 0;
+            }
+            Ok(region)
+        }
+
+        fn data(&self) -> *mut u8 {
+            // SAFETY: the mapping is at least one page larger than the base.
+            unsafe { self.base.as_ptr().add(self.page_size) }
+        }
+
+        pub fn as_slice(&self) -> &[u8] {
+            // SAFETY: the data pages are readable and zero-initialized.
+            unsafe { std::slice::from_raw_parts(self.data(), self.capacity) }
+        }
+
+        pub fn as_mut_slice(&mut self) -> &mut [u8] {
+            // SAFETY: the data pages are writable and owned by `self`.
+            unsafe { std::slice::from_raw_parts_mut(self.data(), self.capacity) }
+        }
+
+        pub fn is_locked(&self) -> bool {
+            self.locked
+        }
+    }
+    impl Drop for Region {
+        fn drop(&mut self) {
+            // SAFETY: unmaps exactly the mapping created in `Region::new`.
+            unsafe {
+                if self.locked {
+                    libc::munlock(self.data().cast(), self.capacity);
+                }
+                libc::munmap(self.base.as_ptr().cast(), self.size);
+            }
+        }
+    }
+
+    fn page_size() -> Result<usize> {
+        // SAFETY: sysconf has no preconditions.
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+        if page_size <= 0 {
+            return Err(last_error("sysconf(_SC_PAGESIZE)"));
+        }
+        Ok(page_size as usize)
+    }
+
+    fn last_error(call: &str) -> Error {
+        Error::MemoryError(format!("{}: {}", call, std::io::Error::last_os_error()))
+    }
+
+    pub fn harden_process() -> Result<()> {
+        let limit = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        // SAFETY: `limit` is a valid rlimit for the duration of the call.
+        if unsafe { libc::setrlimit(libc::RLIMIT_CORE, &limit) } != 0 {
+            return Err(last_error("setrlimit(RLIMIT_CORE)"));
+        }
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        // SAFETY: PR_SET_DUMPABLE takes a single integer argument.
+        if unsafe { libc::prctl(libc::PR_SET_DUMPABLE, 0, 0, 0, 0) } != 0 {
+            return Err(last_error("prctl(PR_SET_DUMPABLE)"));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(unix))]
+mod sys {
+    use crate::Result;
+
+    pub struct Region {
+        bytes: Vec<u8>,
+    }
+    impl Region {
+        pub fn new(len: usize) -> Result<Region> {
+            Ok(Region {
+                bytes: vec![0; len],
+            })
+        }
+
+        pub fn as_slice(&self) -> &[u8] {
+            &self.bytes
+        }
+
+        pub fn as_mut_slice(&mut self) -> &mut [u8] {
+            &mut self.bytes
+        }
+
+        pub fn is_locked(&self) -> bool {
+            false
+        }
+    }
+
+    pub fn harden_process() -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{LockedBytes, Result};
+
+    #[test]
+    fn test_locked_bytes() -> Result<()> {
+        let mut locked = LockedBytes::from_slice(b"key material")?;
+        assert_eq!(locked.len(), 12);
+        assert!(locked.expose(|bytes| bytes == b"key material"));
+        locked.expose_mut(|bytes| bytes[0] = b'K');
+        assert_eq!(locked.try_clone()?, LockedBytes::from_slice(b"Key material")?);
+        assert_eq!(format!("{:?}", locked), "LockedBytes(12 bytes)");
+        assert!(LockedBytes::new(0)?.is_empty());
+        Ok(())
+    }
+
+    /// Hardens a child process running this test alone, so that the
+    /// test process itself stays dumpable.
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_harden_process() -> Result<()> {
+        if std::env::var_os("PASSWORD_KVSTORE_HARDEN_PROCESS").is_some() {
+            crate::harden_process()?;
+            assert_eq!(unsafe { libc::prctl(libc::PR_GET_DUMPABLE, 0, 0, 0, 0) }, 0);
+            return Ok(());
+        }
+        let status = std::process::Command::new(std::env::current_exe()?)
+            .args(["--exact", "memory::tests::test_harden_process"])
+            .env("PASSWORD_KVSTORE_HARDEN_PROCESS", "1")
+            .stdout(std::process::Stdio::null())
+            .status()?;
+        assert!(status.success());
+        Ok(())
+    }
+}
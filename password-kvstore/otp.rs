@@ -0,0 +1,350 @@
+//! One-time passwords as configured by `otpauth://` URIs: RFC 4226
+//! HOTP and RFC 6238 TOTP.
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use hmac::{Hmac, Mac};
+use percent_encoding::percent_decode_str;
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Sha256, Sha512};
+use url::Url;
+
+use crate::{Error, Result, Secret};
+
+pub const DEFAULT_OTP_DIGITS: u32 = 6;
+pub const DEFAULT_OTP_PERIOD: u64 = 30;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum OtpAlgorithm {
+    #[default]
+    Sha1,
+    Sha256,
+    Sha512,
+}
+impl OtpAlgorithm {
+    pub fn name(&self) -> &'static str {
+        match self {
+            OtpAlgorithm::Sha1 => "SHA1",
+            OtpAlgorithm::Sha256 => "SHA256",
+            OtpAlgorithm::Sha512 => "SHA512",
+        }
+    }
+
+    fn mac(&self, key: &[u8], message: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            OtpAlgorithm::Sha1 => mac::<Hmac<Sha1>>(key, message),
+            OtpAlgorithm::Sha256 => mac::<Hmac<Sha256>>(key, message),
+            OtpAlgorithm::Sha512 => mac::<Hmac<Sha512>>(key, message),
+        }
+    }
+}
+impl std::str::FromStr for OtpAlgorithm {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<OtpAlgorithm> {
+        match name.to_ascii_uppercase().as_str() {
+            "SHA1" => Ok(OtpAlgorithm::Sha1),
+            "SHA256" => Ok(OtpAlgorithm::Sha256),
+            "SHA512" => Ok(OtpAlgorithm::Sha512),
+            _ => Err(Error::InvalidOtp(format!("unsupported algorithm {:#?}", name))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum OtpKind {
+    Totp { period: u64 },
+    Hotp { counter: u64 },
+}
+
+/// One-time password generator of an [`Entry`](crate::Entry).
+///
+/// `secret` holds the decoded seed, not its base32 encoding.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Otp {
+    pub kind: OtpKind,
+    pub label: String,
+    pub issuer: Option<String>,
+    pub secret: Secret,
+    pub algorithm: OtpAlgorithm,
+    pub digits: u32,
+}
+impl Otp {
+    /// Parses an `otpauth://totp/...` or `otpauth://hotp/...` URI.
+    ///
+    /// Error messages never quote the URI, as it carries the seed.
+    pub fn from_uri(uri: &str) -> Result<Otp> {
+        let url = Url::parse(uri).map_err(|e| Error::InvalidOtp(format!("invalid URI: {}", e)))?;
+        if url.scheme() != "otpauth" {
+            return Err(Error::InvalidOtp(format!("unsupported scheme {:#?}", url.scheme())));
+        }
+        let mut secret = None;
+        let mut issuer = None;
+        let mut algorithm = OtpAlgorithm::default();
+        let mut digits = DEFAULT_OTP_DIGITS;
+        let mut period = DEFAULT_OTP_PERIOD;
+        let mut counter = None;
+        for (key, value) in url.query_pairs() {
+            match key.as_ref() {
+                "secret" => secret = Some(decode_secret(&value)?),
+                "issuer" => issuer = Some(value.to_string()),
+                "algorithm" => algorithm = value.parse()?,
+                "digits" => digits = parse_parameter("digits", &value)?,
+                "period" => period = parse_parameter("period", &value)?,
+                "counter" => counter = Some(parse_parameter("counter", &value)?),
+                _ => {},
+            }
+        }
+        let kind = match url.host_str() {
+            Some("totp") => OtpKind::Totp { period },
+            Some("hotp") => OtpKind::Hotp {
+                counter: counter.ok_or_else(|| {
+                    Error::InvalidOtp("hotp URI without counter parameter".to_string())
+                })?,
+            },
+            _ => return Err(Error::InvalidOtp("type must be either totp or hotp".to_string())),
+        };
+        let label = percent_decode_str(url.path().trim_start_matches('/'))
+            .decode_utf8()
+            .map_err(|e| Error::InvalidOtp(format!("label: {}", e)))?
+            .to_string();
+        let otp = Otp {
+            kind,
+            label,
+            issuer,
+            secret: secret
+                .ok_or_else(|| Error::InvalidOtp("URI without secret parameter".to_string()))?,
+            algorithm,
+            digits,
+        };
+        otp.validate()?;
+        Ok(otp)
+    }
+
+    /// The `otpauth://` URI of this generator, HOTP counter included.
+    pub fn to_uri(&self) -> Result<Secret> {
+        let (kind, parameter) = match self.kind {
+            OtpKind::Totp { period } => ("totp", ("period", period)),
+            OtpKind::Hotp { counter } => ("hotp", ("counter", counter)),
+        };
+        let mut url = Url::parse(&format!("otpauth://{}/", kind))
+            .map_err(|e| Error::InvalidOtp(format!("invalid URI: {}", e)))?;
+        url.set_path(&self.label);
+        {
+            let mut query = url.query_pairs_mut();
+            self.secret.expose(|secret| {
+                query.append_pair(
+                    "secret",
+                    &base32::encode(base32::Alphabet::Rfc4648 { padding: false }, secret),
+                )
+            });
+            if let Some(issuer) = &self.issuer {
+                query.append_pair("issuer", issuer);
+            }
+            query
+                .append_pair("algorithm", self.algorithm.name())
+                .append_pair("digits", &self.digits.to_string())
+                .append_pair(parameter.0, &parameter.1.to_string());
+        }
+        Ok(Secret::from(String::from(url)))
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !(6..=10).contains(&self.digits) {
+            return Err(Error::InvalidOtp(format!(
+                "digits must be between 6 and 10, got {}",
+                self.digits
+            )));
+        }
+        if self.kind == (OtpKind::Totp { period: 0 }) {
+            return Err(Error::InvalidOtp("period must not be zero".to_string()));
+        }
+        if self.secret.is_empty() {
+            return Err(Error::InvalidOtp("secret must not be empty".to_string()));
+        }
+        Ok(())
+    }
+
+    /// RFC 4226 HOTP value for `counter`.
+    pub fn hotp(&self, counter: u64) -> Result<String> {
+        self.validate()?;
+        let digest = self
+            .secret
+            .expose(|secret| self.algorithm.mac(secret, &counter.to_be_bytes()))?;
+        let offset = (digest[digest.len() - 1] & 0x0F) as usize;
+        let binary = u32::from_be_bytes([
+            digest[offset] & 0x7F,
+            digest[offset + 1],
+            digest[offset + 2],
+            digest[offset + 3],
+        ]);
+        let code = u64::from(binary) % 10u64.pow(self.digits);
+        Ok(format!("{:0width$}", code, width = self.digits as usize))
+    }
+
+    /// RFC 6238 TOTP value at `time`.
+    pub fn totp(&self, time: SystemTime) -> Result<String> {
+        match self.kind {
+            OtpKind::Totp { period } if period > 0 => {
+                let seconds = time
+                    .duration_since(UNIX_EPOCH)
+                    .map_err(|e| Error::InvalidOtp(format!("time before epoch: {}", e)))?
+                    .as_secs();
+                self.hotp(seconds / period)
+            },
+            OtpKind::Totp { .. } => Err(Error::InvalidOtp("period must not be zero".to_string())),
+            OtpKind::Hotp { .. } => Err(Error::InvalidOtp("not a totp generator".to_string())),
+        }
+    }
+
+    /// The current code: TOTP at the current time, or HOTP at the
+    /// current counter, which is then incremented.
+    pub fn generate(&mut self) -> Result<String> {
+        match self.kind {
+            OtpKind::Totp { .. } => self.totp(SystemTime::now()),
+            OtpKind::Hotp { counter } => {
+                let code = self.hotp(counter)?;
+                self.kind = OtpKind::Hotp {
+                    counter: counter
+                        .checked_add(1)
+                        .ok_or_else(|| Error::InvalidOtp("hotp counter overflow".to_string()))?,
+                };
+                Ok(code)
+            },
+        }
+    }
+}
+
+fn mac<M: Mac + hmac::digest::KeyInit>(key: &[u8], message: &[u8]) -> Result<Vec<u8>> {
+    let mut mac = <M as Mac>::new_from_slice(key)
+        .map_err(|e| Error::InvalidKeyError(format!("otp secret: {}", e)))?;
+    mac.update(message);
+    Ok(mac.finalize().into_bytes().to_vec())
+}
+
+fn decode_secret(encoded: &str) -> Result<Secret> {
+    let normalized = encoded
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '=')
+        .map(|c| c.to_ascii_uppercase())
+        .collect::<String>();
+    match base32::decode(base32::Alphabet::Rfc4648 { padding: false }, &normalized) {
+        Some(secret) if !secret.is_empty() => Ok(Secret::from(secret)),
+        _ => Err(Error::InvalidOtp("secret is not valid base32".to_string())),
+    }
+}
+
+fn parse_parameter<T: std::str::FromStr>(name: &str, value: &str) -> Result<T> {
+    value
+        .parse()
+        .map_err(|_| Error::InvalidOtp(format!("invalid {} {:#?}", name, value)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use crate::{Error, Otp, OtpAlgorithm, OtpKind, Result, Secret};
+
+    fn otp(kind: OtpKind, secret: &str, algorithm: OtpAlgorithm, digits: u32) -> Otp {
+        Otp {
+            kind,
+            label: String::new(),
+            issuer: None,
+            secret: Secret::from(secret),
+            algorithm,
+            digits,
+        }
+    }
+
+    #[test]
+    fn test_hotp_rfc4226() -> Result<()> {
+        let otp = otp(OtpKind::Hotp { counter: 0 }, "12345678901234567890", OtpAlgorithm::Sha1, 6);
+        let expected = [
+            "755224", "287082", "359152", "969429", "338314", "254676", "287922", "162583",
+            "399871", "520489",
+        ];
+        for (counter, code) in expected.iter().enumerate() {
+            assert_eq!(otp.hotp(counter as u64)?, *code);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_totp_rfc6238() -> Result<()> {
+        let seeds = [
+            (
+                OtpAlgorithm::Sha1,
+                "12345678901234567890",
+                ["94287082", "07081804", "65353130"],
+            ),
+            (
+                OtpAlgorithm::Sha256,
+                "12345678901234567890123456789012",
+                ["46119246", "68084774", "77737706"],
+            ),
+            (
+                OtpAlgorithm::Sha512,
+                "1234567890123456789012345678901234567890123456789012345678901234",
+                ["90693936", "25091201", "47863826"],
+            ),
+        ];
+        for (algorithm, seed, codes) in seeds {
+            let otp = otp(OtpKind::Totp { period: 30 }, seed, algorithm, 8);
+            for (seconds, code) in [59, 1111111109, 20000000000].iter().zip(codes) {
+                assert_eq!(otp.totp(UNIX_EPOCH + Duration::from_secs(*seconds))?, code);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_otp_uri() -> Result<()> {
+        let otp = Otp::from_uri(
+            "otpauth://totp/ACME%20Co:john@example.com?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ\
+             &issuer=ACME%20Co&algorithm=SHA256&digits=8&period=60",
+        )?;
+        assert_eq!(otp.kind, OtpKind::Totp { period: 60 });
+        assert_eq!(otp.label, "ACME Co:john@example.com");
+        assert_eq!(otp.issuer.as_deref(), Some("ACME Co"));
+        assert_eq!(otp.secret, Secret::from("12345678901234567890"));
+        assert_eq!(otp.algorithm, OtpAlgorithm::Sha256);
+        assert_eq!(otp.digits, 8);
+        let uri = otp.to_uri()?;
+        assert_eq!(uri.expose_str(Otp::from_uri)??, otp);
+
+        let otp = Otp::from_uri("otpauth://hotp/label?secret=gezdgnbvgy3tqojq&counter=7")?;
+        assert_eq!(otp.kind, OtpKind::Hotp { counter: 7 });
+        assert_eq!((otp.algorithm, otp.digits), (OtpAlgorithm::Sha1, 6));
+        Ok(())
+    }
+
+    #[test]
+    fn test_otp_uri_errors() {
+        for uri in [
+            "https://totp/label?secret=GEZDGNBV",
+            "otpauth://motp/label?secret=GEZDGNBV",
+            "otpauth://totp/label",
+            "otpauth://totp/label?secret=not-base32!",
+            "otpauth://totp/label?secret=GEZDGNBV&digits=4",
+            "otpauth://totp/label?secret=GEZDGNBV&period=0",
+            "otpauth://totp/label?secret=GEZDGNBV&algorithm=MD5",
+            "otpauth://hotp/label?secret=GEZDGNBV",
+        ] {
+            match Otp::from_uri(uri) {
+                Err(Error::InvalidOtp(message)) => assert!(!message.contains("GEZDGNBV")),
+                other => panic!("{} parsed as {:?}", uri, other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_otp_generate_increments_hotp_counter() -> Result<()> {
+        let mut otp =
+            otp(OtpKind::Hotp { counter: 1 }, "12345678901234567890", OtpAlgorithm::Sha1, 6);
+        assert_eq!(otp.generate()?, "287082");
+        assert_eq!(otp.generate()?, "359152");
+        assert_eq!(otp.kind, OtpKind::Hotp { counter: 3 });
+        Ok(())
+    }
+}
@@ -0,0 +1,312 @@
+//! Queries over decrypted [`Entry`] fields, see [`Folder::search`](crate::Folder::search).
+//!
+//! A query is a whitespace-separated list of terms, every one of which
+//! must match for an entry to be returned. A term is either plain text,
+//! matched against every searchable field, or scoped to one field as in
+//! `user:alice url:github.com`. Double quotes group words into a single
+//! term: `notes:"on call"`.
+//!
+//! Terms match a field exactly, as a prefix, as a substring or, for terms
+//! of at least [`FUZZY_MIN_LENGTH`] characters, as a subsequence, in
+//! decreasing order of score. Matching ignores case.
+//!
+//! [`Secret`](crate::Secret) values (the password and attribute values)
+//! are only searched when the query is built
+//! [`with_secrets`](SearchQuery::with_secrets); attribute names are
+//! always searched.
+use zeroize::Zeroizing;
+
+use crate::{Entry, Error, Result};
+
+/// Minimum length of a term for subsequence ("fuzzy") matches.
+pub const FUZZY_MIN_LENGTH: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchField {
+    Name,
+    Username,
+    Email,
+    Url,
+    Description,
+    Notes,
+    Tag,
+    Attribute,
+    Secret,
+}
+impl SearchField {
+    /// Fields searched by unscoped terms, [`Secret`](SearchField::Secret)
+    /// excepted.
+    pub const PUBLIC: [SearchField; 8] = [
+        SearchField::Name,
+        SearchField::Username,
+        SearchField::Email,
+        SearchField::Url,
+        SearchField::Description,
+        SearchField::Notes,
+        SearchField::Tag,
+        SearchField::Attribute,
+    ];
+
+    fn weight(&self) -> u32 {
+        match self {
+            SearchField::Name => 3,
+            SearchField::Username | SearchField::Email | SearchField::Tag => 2,
+            _ => 1,
+        }
+    }
+
+    /// Best score of `text` against the values of this field of `entry`.
+    fn score(&self, text: &str, entry: &Entry) -> Option<u32> {
+        let best = |values: &mut dyn Iterator<Item = &str>| {
+            values.filter_map(|value| score(text, value)).max()
+        };
+        let score = match self {
+            SearchField::Name => score(text, &entry.name),
+            SearchField::Username => score(text, &entry.username),
+            SearchField::Email => score(text, &entry.email),
+            SearchField::Url => best(&mut entry.urls.iter().map(String::as_str)),
+            SearchField::Description => score(text, &entry.description),
+            SearchField::Notes => best(&mut entry.notes.lines()),
+            SearchField::Tag => best(&mut entry.tags.iter().map(String::as_str)),
+            SearchField::Attribute => best(&mut entry.attributes.keys().map(String::as_str)),
+            SearchField::Secret => std::iter::once(&entry.password)
+                .chain(entry.attributes.values())
+                .filter_map(|secret| secret.expose_str(|value| score(text, value)).ok().flatten())
+                .max(),
+        };
+        score.map(|score| score * self.weight())
+    }
+}
+impl std::str::FromStr for SearchField {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<SearchField> {
+        match name.to_ascii_lowercase().as_str() {
+            "name" => Ok(SearchField::Name),
+            "user" | "username" => Ok(SearchField::Username),
+            "email" => Ok(SearchField::Email),
+            "url" => Ok(SearchField::Url),
+            "desc" | "description" => Ok(SearchField::Description),
+            "note" | "notes" => Ok(SearchField::Notes),
+            "tag" => Ok(SearchField::Tag),
+            "attr" | "attribute" => Ok(SearchField::Attribute),
+            "secret" | "password" => Ok(SearchField::Secret),
+            _ => Err(Error::InvalidFormat(format!("unknown search field {:#?}", name))),
+        }
+    }
+}
+
+/// One term of a [`SearchQuery`], lowercased.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchTerm {
+    pub field: Option<SearchField>,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SearchQuery {
+    pub terms: Vec<SearchTerm>,
+    pub include_secrets: bool,
+}
+impl SearchQuery {
+    pub fn parse(query: &str) -> Result<SearchQuery> {
+        let mut terms = Vec::new();
+        for token in tokenize(query)? {
+            let term = match token.split_once(':') {
+                Some((field, text)) if field.parse::<SearchField>().is_ok() => {
+                    if text.is_empty() {
+                        return Err(Error::InvalidFormat(format!(
+                            "empty search term for field {:#?}",
+                            field
+                        )));
+                    }
+                    SearchTerm {
+                        field: Some(field.parse()?),
+                        text: text.to_lowercase(),
+                    }
+                },
+                _ => SearchTerm {
+                    field: None,
+                    text: token.to_lowercase(),
+                },
+            };
+            terms.push(term);
+        }
+        Ok(SearchQuery {
+            terms,
+            include_secrets: false,
+        })
+    }
+
+    /// Also matches unscoped terms against secret values and allows
+    /// `secret:` terms.
+    pub fn with_secrets(mut self) -> SearchQuery {
+        self.include_secrets = true;
+        self
+    }
+
+    /// Fails for `secret:` terms of a query not built
+    /// [`with_secrets`](SearchQuery::with_secrets).
+    pub fn validate(&self) -> Result<()> {
+        if !self.include_secrets
+            && self.terms.iter().any(|term| term.field == Some(SearchField::Secret))
+        {
+            return Err(Error::InvalidFormat(
+                "searching secret values requires SearchQuery::with_secrets".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Score of `entry`, `None` unless every term matches.
+    pub fn score(&self, entry: &Entry) -> Option<u32> {
+        let mut total = 0;
+        for term in &self.terms {
+            let score = match term.field {
+                Some(field) => field.score(&term.text, entry),
+                None => SearchField::PUBLIC
+                    .iter()
+                    .chain(self.include_secrets.then_some(&SearchField::Secret))
+                    .filter_map(|field| field.score(&term.text, entry))
+                    .max(),
+            };
+            total += score?;
+        }
+        Some(total)
+    }
+}
+impl std::str::FromStr for SearchQuery {
+    type Err = Error;
+
+    fn from_str(query: &str) -> Result<SearchQuery> {
+        SearchQuery::parse(query)
+    }
+}
+
+/// An entry matched by a [`SearchQuery`] and its score, higher first.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SearchMatch {
+    pub entry: Entry,
+    pub score: u32,
+}
+
+/// Splits `query` on whitespace outside of double quotes.
+fn tokenize(query: &str) -> Result<Vec<String>> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    let mut quoted = false;
+    for c in query.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted =>
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                },
+            c => token.push(c),
+        }
+    }
+    if quoted {
+        return Err(Error::InvalidFormat("unterminated quote in search query".to_string()));
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+/// Score of the lowercase `text` against `value`.
+///
+/// The lowercased copy of `value` is zeroized, as it may be a secret.
+fn score(text: &str, value: &str) -> Option<u32> {
+    let value = Zeroizing::new(value.to_lowercase());
+    if *value == text {
+        Some(100)
+    } else if value.starts_with(text) {
+        Some(80)
+    } else if value.contains(text) {
+        Some(60)
+    } else if text.chars().count() >= FUZZY_MIN_LENGTH {
+        fuzzy_score(text, &value)
+    } else {
+        None
+    }
+}
+
+/// Scores `text` as a subsequence of `value`, less for every character
+/// skipped between its first and last matched characters.
+fn fuzzy_score(text: &str, value: &str) -> Option<u32> {
+    let mut chars = value.chars().enumerate();
+    let mut first = None;
+    let mut last = 0;
+    for c in text.chars() {
+        let (position, _) = chars.find(|(_, candidate)| *candidate == c)?;
+        first.get_or_insert(position);
+        last = position;
+    }
+    let skipped = (last + 1 - first?) - text.chars().count();
+    Some(40u32.saturating_sub(2 * skipped as u32).max(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Entry, Error, Result, SearchField, SearchQuery, SearchTerm, Secret};
+
+    #[test]
+    fn test_search_query_parse() -> Result<()> {
+        let query = SearchQuery::parse(r#"user:Alice  url:github.com "on call" https://x.io"#)?;
+        assert_eq!(
+            query.terms,
+            vec![
+                SearchTerm {
+                    field: Some(SearchField::Username),
+                    text: "alice".to_string()
+                },
+                SearchTerm {
+                    field: Some(SearchField::Url),
+                    text: "github.com".to_string()
+                },
+                SearchTerm {
+                    field: None,
+                    text: "on call".to_string()
+                },
+                SearchTerm {
+                    field: None,
+                    text: "https://x.io".to_string()
+                },
+            ]
+        );
+        assert!(matches!(SearchQuery::parse("user:"), Err(Error::InvalidFormat(_))));
+        assert!(matches!(SearchQuery::parse("\"open"), Err(Error::InvalidFormat(_))));
+        assert!(matches!(
+            SearchQuery::parse("secret:x")?.validate(),
+            Err(Error::InvalidFormat(_))
+        ));
+        SearchQuery::parse("secret:x")?.with_secrets().validate()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_query_score() -> Result<()> {
+        let mut entry = Entry::new("GitHub");
+        entry.username = "alice".to_string();
+        entry.password = Secret::from("hunter2");
+        entry.urls = vec!["https://github.com/login".to_string()];
+        entry.attributes.insert("recovery-code".to_string(), Secret::from("r3c0very"));
+
+        let score = |query: &str| SearchQuery::parse(query).unwrap().score(&entry);
+        assert_eq!(score("github"), Some(300));
+        assert_eq!(score("git"), Some(240));
+        assert!(score("gthb") > Some(0));
+        assert_eq!(score("gh"), None);
+        assert_eq!(score("user:alice url:github.com"), Some(260));
+        assert_eq!(score("user:github"), None);
+        assert_eq!(score("recovery"), Some(80));
+        assert_eq!(score("hunter2"), None);
+        assert_eq!(score("r3c0very"), None);
+
+        let query = SearchQuery::parse("hunter2")?.with_secrets();
+        assert_eq!(query.score(&entry), Some(100));
+        assert_eq!(SearchQuery::parse("password:hunt")?.with_secrets().score(&entry), Some(80));
+        Ok(())
+    }
+}
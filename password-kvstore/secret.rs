@@ -1,31 +1,72 @@
 use std::fmt::{Debug, Display};
 
-use sanitation::SString;
 use serde::{Deserialize, Serialize};
+use subtle::{Choice, ConstantTimeEq};
+use zeroize::{Zeroize, ZeroizeOnDrop};
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
+use crate::{Error, Result};
+
+/// Plaintext secret value of an [`Entry`](crate::Entry).
+///
+/// The bytes are wiped when the `Secret` is dropped and are only
+/// reachable through [`expose`](Secret::expose) and
+/// [`expose_str`](Secret::expose_str), which lend them to a closure
+/// instead of handing out copies.
+#[derive(Serialize, Deserialize, Default, Eq)]
 pub struct Secret {
     data: Vec<u8>,
     len: usize,
 }
 impl Secret {
     pub fn from_plaintext(plaintext: impl Display) -> Secret {
-        let data = plaintext.to_string().as_bytes().to_vec();
+        let data = plaintext.to_string().into_bytes();
         let len = data.len();
         Secret { data, len }
     }
 
-    pub fn plaintext(&self) -> String {
-        SString::new(&self.data).unchecked_safe()
+    pub fn expose<T>(&self, f: impl FnOnce(&[u8]) -> T) -> T {
+        f(&self.data)
     }
 
-    pub fn as_bytes(&self) -> Vec<u8> {
-        self.data.clone()
+    pub fn expose_str<T>(&self, f: impl FnOnce(&str) -> T) -> Result<T> {
+        let plaintext =
+            std::str::from_utf8(&self.data).map_err(|e| Error::InvalidUtf8(format!("{}", e)))?;
+        Ok(f(plaintext))
     }
 
     pub fn len(&self) -> usize {
         self.len
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Copies the secret. `Secret` does not implement `Clone` so that
+    /// every copy of a plaintext is explicit.
+    pub fn duplicate(&self) -> Secret {
+        Secret {
+            data: self.data.clone(),
+            len: self.len,
+        }
+    }
+}
+impl Drop for Secret {
+    fn drop(&mut self) {
+        self.data.zeroize();
+        self.len.zeroize();
+    }
+}
+impl ZeroizeOnDrop for Secret {}
+impl PartialEq for Secret {
+    fn eq(&self, other: &Secret) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+impl ConstantTimeEq for Secret {
+    fn ct_eq(&self, other: &Secret) -> Choice {
+        self.data.as_slice().ct_eq(other.data.as_slice()) & self.len.ct_eq(&other.len)
+    }
 }
 impl From<&str> for Secret {
     fn from(t: &str) -> Secret {
@@ -34,7 +75,15 @@ impl From<&str> for Secret {
 }
 impl From<String> for Secret {
     fn from(t: String) -> Secret {
-        Secret::from_plaintext(t)
+        let data = t.into_bytes();
+        let len = data.len();
+        Secret { data, len }
+    }
+}
+impl From<Vec<u8>> for Secret {
+    fn from(data: Vec<u8>) -> Secret {
+        let len = data.len();
+        Secret { data, len }
     }
 }
 impl Display for Secret {
@@ -42,14 +91,35 @@ impl Display for Secret {
         write!(f, "{}", (0..self.len).map(|_| '*').collect::<String>())
     }
 }
+impl Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Secret({})", self)
+    }
+}
 
 #[cfg(test)]
 mod tests {
-    use crate::Secret;
+    use crate::{Result, Secret};
 
     #[test]
     fn test_secret() {
         let secret = Secret::from("secret");
         assert_eq!(secret.to_string(), "******");
+        assert_eq!(format!("{:?}", secret), "Secret(******)");
+    }
+
+    #[test]
+    fn test_secret_eq() {
+        assert_eq!(Secret::from("secret"), Secret::from("secret"));
+        assert_ne!(Secret::from("secret"), Secret::from("secreT"));
+        assert_ne!(Secret::from("secret"), Secret::from("secrets"));
+    }
+
+    #[test]
+    fn test_secret_expose() -> Result<()> {
+        let secret = Secret::from("secret");
+        assert!(secret.expose(|bytes| bytes == b"secret"));
+        assert_eq!(secret.expose_str(|plaintext| plaintext.len())?, 6);
+        Ok(())
     }
 }
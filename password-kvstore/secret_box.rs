@@ -1,37 +1,120 @@
-use crate::{Data, Result};
-use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
-use chacha20poly1305::{ChaCha20Poly1305};
-use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+use chacha20poly1305::aead::{Aead, AeadCore, AeadInPlace, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Tag};
+use rand::RngCore;
+use subtle::{Choice, ConstantTimeEq};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use crate::{Data, Error, LockedBytes, Result};
+
+const TAG_LENGTH: usize = 16;
+
+/// In-memory wrapping of a secret under a random, process-local key.
+///
+/// The key is shared by every `SecretBox` and lives in [`LockedBytes`];
+/// [`expose`](SecretBox::expose) decrypts into locked memory as well, so
+/// neither the key nor the plaintext is swapped out or dumped.
+///
+/// `SecretBox` deliberately does not implement `Serialize`: its key is
+/// process-local and never reaches the disk. Persisted types carry the wrapped
+/// ciphertext instead, see [`StoredFolder`](crate::StoredFolder).
+#[derive(Eq)]
 pub struct SecretBox {
     pub ciphertext: Data,
-    pub key: [u8; 32],
     pub nonce: [u8; 12],
 }
 impl SecretBox {
     pub fn close(data: &[u8]) -> Result<SecretBox> {
-        let key = ChaCha20Poly1305::generate_key(&mut OsRng);
-        let cipher = ChaCha20Poly1305::new(&key);
         let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
-        let ciphertext = Data::new(cipher.encrypt(&nonce, data)?);
+        let ciphertext = Data::new(cipher()?.encrypt(&nonce, data)?);
         Ok(SecretBox {
-            key: key.into(),
             nonce: nonce.into(),
             ciphertext,
         })
     }
 
+    /// Copies the wrapped ciphertext, which exposes no plaintext.
+    pub fn duplicate(&self) -> SecretBox {
+        SecretBox {
+            ciphertext: self.ciphertext.duplicate(),
+            nonce: self.nonce,
+        }
+    }
+
     pub fn open(&self) -> Result<Data> {
-        let cipher = ChaCha20Poly1305::new((&self.key).into());
-        let plaintext = cipher.decrypt((&self.nonce).into(), self.ciphertext.bytes.as_slice())?;
+        let plaintext =
+            cipher()?.decrypt((&self.nonce).into(), self.ciphertext.bytes.as_slice())?;
         Ok(Data::new(plaintext))
     }
+
+    /// Lends the plaintext to `f`, decrypted into [`LockedBytes`] that
+    /// are wiped once `f` returns.
+    pub fn expose<T>(&self, f: impl FnOnce(&[u8]) -> T) -> Result<T> {
+        let tag_offset = self.ciphertext.len().checked_sub(TAG_LENGTH).ok_or_else(|| {
+            Error::DecryptionError("SecretBox ciphertext is too short".to_string())
+        })?;
+        let (ciphertext, tag) = self.ciphertext.bytes.split_at(tag_offset);
+        let mut plaintext = LockedBytes::from_slice(ciphertext)?;
+        let cipher = cipher()?;
+        plaintext.expose_mut(|buffer| {
+            cipher.decrypt_in_place_detached(
+                (&self.nonce).into(),
+                b"",
+                buffer,
+                Tag::from_slice(tag),
+            )
+        })?;
+        Ok(plaintext.expose(f))
+    }
+}
+impl Drop for SecretBox {
+    fn drop(&mut self) {
+        self.nonce.zeroize();
+    }
+}
+impl ZeroizeOnDrop for SecretBox {}
+impl PartialEq for SecretBox {
+    fn eq(&self, other: &SecretBox) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+impl ConstantTimeEq for SecretBox {
+    fn ct_eq(&self, other: &SecretBox) -> Choice {
+        self.ciphertext.ct_eq(&other.ciphertext) & self.nonce.ct_eq(&other.nonce)
+    }
+}
+impl std::fmt::Debug for SecretBox {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("SecretBox")
+            .field("ciphertext", &self.ciphertext)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Key shared by every [`SecretBox`], generated on first use.
+fn key() -> Result<&'static LockedBytes> {
+    static KEY: OnceLock<LockedBytes> = OnceLock::new();
+    if let Some(key) = KEY.get() {
+        return Ok(key);
+    }
+    let mut key = LockedBytes::new(32)?;
+    key.expose_mut(|bytes| rand::rng().fill_bytes(bytes));
+    Ok(KEY.get_or_init(|| key))
+}
+
+fn cipher() -> Result<ChaCha20Poly1305> {
+    key()?
+        .expose(ChaCha20Poly1305::new_from_slice)
+        .map_err(|e| Error::InvalidKeyError(format!("SecretBox key: {}", e)))
 }
 
 #[test]
 fn test_secretbox() -> Result<()> {
     let secret = SecretBox::close(b"secret")?;
     assert_eq!(secret.open()?.to_bytes(), b"secret".to_vec());
+    assert!(secret.expose(|bytes| bytes == b"secret")?);
+    assert!(!format!("{:?}", secret).contains("key"));
+    assert_ne!(SecretBox::close(b"secret")?, secret);
     Ok(())
 }
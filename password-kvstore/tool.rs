@@ -1,15 +1,25 @@
+use std::sync::OnceLock;
+
 use argon2_kdf::{Algorithm, Hasher};
 use chacha20::cipher::{KeyIvInit, StreamCipher, StreamCipherSeek};
 use chacha20::ChaCha20;
+use chacha20poly1305::aead::{AeadCore, OsRng};
+use chacha20poly1305::ChaCha20Poly1305;
 use pbkdf2::pbkdf2_hmac;
 use serde::{Deserialize, Serialize};
 use sha2::Sha256;
 use sha3::{Digest, Sha3_384};
+use zeroize::{Zeroize, Zeroizing};
 
-use crate::{Data, Result, SecretBox};
+use crate::{
+    Aes256GcmSivKey, ChaCha20Poly1305Key, Cipher, CipherId, Data, DataSeq, DecryptionKey,
+    EncryptionKey, Error, KdfAlgorithm, KdfParams, LockedBytes, PlainBytes, Result, SecretBox,
+    XChaCha20Poly1305Key,
+};
 
+pub const DEFAULT_ITERATIONS: u32 = 600_000;
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct CipherText {
     ciphertext: Data,
     nonce: [u8; 12],
@@ -22,47 +32,149 @@ impl CipherText {
 
     }
     pub fn ciphertext(&self) -> Data {
-        self.ciphertext.clone()
+        self.ciphertext.duplicate()
     }
     pub fn nonce(&self) -> [u8;12] {
         self.nonce.clone()
     }
+
+    pub fn to_data_seq(&self) -> DataSeq {
+        let mut seq = DataSeq::new();
+        seq.push(Data::from(self.nonce.as_slice()));
+        seq.push(self.ciphertext.duplicate());
+        seq
+    }
+
+    pub fn from_data_seq(seq: DataSeq) -> Result<CipherText> {
+        if seq.len() != 2 {
+            return Err(Error::DecodingError(format!(
+                "expected nonce and ciphertext, got {} chunks",
+                seq.len()
+            )));
+        }
+        let nonce = seq[0].bytes.as_slice().try_into().map_err(|_| {
+            Error::DecodingError(format!("expected a 12 byte nonce, got {} bytes", seq[0].len()))
+        })?;
+        Ok(CipherText::new(seq[1].duplicate(), nonce))
+    }
 }
+impl PlainBytes for CipherText {}
 
 pub struct Chacha20Tool {
     password: SecretBox,
-    iterations: u32,
+    params: KdfParams,
+    cipher: CipherId,
+    salt: Option<Vec<u8>>,
+    key: OnceLock<MasterKey>,
+}
+
+/// The derived master key along with the AEAD key built from it.
+struct MasterKey {
+    key: LockedBytes,
+    aead: Box<dyn Cipher + Send + Sync>,
 }
 
 impl Chacha20Tool {
     pub fn new(password: &str, iterations: u32) -> Result<Chacha20Tool> {
+        Chacha20Tool::with_params(password, KdfParams::with_iterations(iterations))
+    }
+
+    pub fn builder() -> Chacha20ToolBuilder {
+        Chacha20ToolBuilder::new()
+    }
+
+    pub fn with_params(password: &str, params: KdfParams) -> Result<Chacha20Tool> {
+        params.validate()?;
         Ok(Chacha20Tool {
-            password: SecretBox::close(&password.as_bytes())?,
-            iterations: iterations,
+            password: SecretBox::close(password.as_bytes())?,
+            params,
+            cipher: CipherId::ChaCha20Poly1305,
+            salt: None,
+            key: OnceLock::new(),
         })
     }
 
+    pub fn with_salt(mut self, salt: &[u8]) -> Chacha20Tool {
+        self.salt = Some(salt.to_vec());
+        self.key = OnceLock::new();
+        self
+    }
+
+    pub fn with_cipher(mut self, cipher: CipherId) -> Chacha20Tool {
+        self.cipher = cipher;
+        self.key = OnceLock::new();
+        self
+    }
+
+    pub fn params(&self) -> KdfParams {
+        self.params.clone()
+    }
+
     pub fn hash(&self) -> Result<(Vec<u8>, Vec<u8>)> {
-        let bytes = self.password.open()?.to_bytes();
-        let hash = Hasher::new()
-            .algorithm(Algorithm::Argon2id)
-            .salt_length(12)
-            .hash_length(42)
-            .iterations(12)
-            .memory_cost_kib(125000)
-            .threads(2)
-            .hash(&bytes)?;
-        Ok((hash.as_bytes().to_vec(), hash.salt_bytes().to_vec()))
-    }
-
-    pub fn key(&self) -> Result<[u8; 32]> {
-        let mut key = [0; 32];
-        let password = bincode::serialize(&self.password.open()?.bytes)?;
-        let mut sha3_384 = Sha3_384::new();
-        sha3_384.update(&password);
-        let salt = sha3_384.finalize().to_vec();
-        pbkdf2_hmac::<Sha256>(&password, &salt, self.iterations, &mut key);
-        Ok(key)
+        self.password.expose(|bytes| -> Result<(Vec<u8>, Vec<u8>)> {
+            let hash = Hasher::new()
+                .algorithm(Algorithm::Argon2id)
+                .salt_length(self.params.argon2_salt_length)
+                .hash_length(self.params.argon2_hash_length)
+                .iterations(self.params.argon2_iterations)
+                .memory_cost_kib(self.params.argon2_memory_kib)
+                .threads(self.params.argon2_threads)
+                .hash(bytes)?;
+            Ok((hash.as_bytes().to_vec(), hash.salt_bytes().to_vec()))
+        })?
+    }
+
+    pub fn unlock(self) -> Result<Chacha20Tool> {
+        self.expose_key(|_| ())?;
+        Ok(self)
+    }
+
+    /// Lends the master key to `f`, deriving it on first use. The key is
+    /// never handed out by value.
+    pub fn expose_key<T>(&self, f: impl FnOnce(&[u8; 32]) -> T) -> Result<T> {
+        self.master_key()?.key.expose(|bytes| match <&[u8; 32]>::try_from(bytes) {
+            Ok(key) => Ok(f(key)),
+            Err(_) => Err(Error::InvalidKeyError(format!(
+                "expected a 32 byte key, got {} bytes",
+                bytes.len()
+            ))),
+        })
+    }
+
+    fn master_key(&self) -> Result<&MasterKey> {
+        if let Some(key) = self.key.get() {
+            return Ok(key);
+        }
+        let mut key = self.derive_key()?;
+        let master_key = self.build_master_key(&key);
+        key.zeroize();
+        let master_key = master_key?;
+        Ok(self.key.get_or_init(|| master_key))
+    }
+
+    fn build_master_key(&self, key: &[u8; 32]) -> Result<MasterKey> {
+        let aead: Box<dyn Cipher + Send + Sync> = match self.cipher {
+            CipherId::ChaCha20Poly1305 => Box::new(ChaCha20Poly1305Key::new(key)?),
+            CipherId::XChaCha20Poly1305 => Box::new(XChaCha20Poly1305Key::new(key)?),
+            CipherId::Aes256GcmSiv => Box::new(Aes256GcmSivKey::new(key)?),
+        };
+        Ok(MasterKey {
+            key: LockedBytes::from_slice(key)?,
+            aead,
+        })
+    }
+
+    fn derive_key(&self) -> Result<[u8; 32]> {
+        let password = Zeroizing::new(self.password.expose(bincode::serialize)??);
+        let salt = match &self.salt {
+            Some(salt) => salt.clone(),
+            None => {
+                let mut sha3_384 = Sha3_384::new();
+                sha3_384.update(&password);
+                sha3_384.finalize().to_vec()
+            },
+        };
+        self.params.derive(&password, &salt)
     }
 
     pub fn nonce(&self) -> Result<[u8; 12]> {
@@ -71,7 +183,7 @@ impl Chacha20Tool {
         pbkdf2_hmac::<Sha256>(
             &hash_bytes,
             &salt_bytes,
-            self.iterations,
+            self.params.iterations,
             &mut nonce,
         );
 
@@ -79,14 +191,17 @@ impl Chacha20Tool {
     }
 
     fn chacha20(&self, nonce: &[u8; 12]) -> Result<ChaCha20> {
-        let mut chacha20 = ChaCha20::new(&self.key()?.into(), nonce.into());
-        chacha20.seek(self.iterations);
-        chacha20.seek(self.password.open()?.len());
-        Ok(chacha20)
+        let password_length = self.password.expose(|bytes| bytes.len())?;
+        self.expose_key(|key| {
+            let mut chacha20 = ChaCha20::new(key.into(), nonce.into());
+            chacha20.seek(self.params.iterations);
+            chacha20.seek(password_length);
+            chacha20
+        })
     }
 
     pub fn encrypt(&self, data: &[u8]) -> Result<CipherText> {
-        let nonce = self.nonce()?;
+        let nonce: [u8; 12] = ChaCha20Poly1305::generate_nonce(&mut OsRng).into();
         let mut chacha20 = self.chacha20(&nonce)?;
         let mut ciphertext = data.to_vec();
         chacha20.apply_keystream(&mut ciphertext);
@@ -102,6 +217,120 @@ impl Chacha20Tool {
         chacha20.apply_keystream(&mut plaintext);
         Ok(plaintext)
     }
+
+    pub fn seal(&self, data: &[u8], aad: &[u8]) -> Result<CipherText> {
+        CipherText::from_data_seq(self.encrypt_bytes(data, aad)?)
+    }
+
+    pub fn unseal(&self, data: &CipherText, aad: &[u8]) -> Result<Vec<u8>> {
+        Ok(self.decrypt_bytes(data.to_data_seq(), aad)?[0].to_vec())
+    }
+
+    fn aead_key(&self) -> Result<&dyn Cipher> {
+        Ok(self.master_key()?.aead.as_ref())
+    }
+}
+impl EncryptionKey for Chacha20Tool {
+    fn cipher(&self) -> CipherId {
+        self.cipher
+    }
+
+    fn encrypt_bytes(&self, data: &[u8], aad: &[u8]) -> Result<DataSeq> {
+        self.aead_key()?.encrypt_bytes(data, aad)
+    }
+
+    fn blind_index(&self, data: &[u8]) -> Result<Data> {
+        self.expose_key(|key| crate::cipher::blind_index(key, data))?
+    }
+}
+impl DecryptionKey for Chacha20Tool {
+    fn decrypt_bytes(&self, data: DataSeq, aad: &[u8]) -> Result<DataSeq> {
+        self.aead_key()?.decrypt_bytes(data, aad)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Chacha20ToolBuilder {
+    params: KdfParams,
+    cipher: CipherId,
+    salt: Option<Vec<u8>>,
+}
+impl Default for Chacha20ToolBuilder {
+    fn default() -> Chacha20ToolBuilder {
+        Chacha20ToolBuilder {
+            params: KdfParams::default(),
+            cipher: CipherId::ChaCha20Poly1305,
+            salt: None,
+        }
+    }
+}
+impl Chacha20ToolBuilder {
+    pub fn new() -> Chacha20ToolBuilder {
+        Chacha20ToolBuilder::default()
+    }
+
+    pub fn params(mut self, params: KdfParams) -> Chacha20ToolBuilder {
+        self.params = params;
+        self
+    }
+
+    pub fn algorithm(mut self, algorithm: KdfAlgorithm) -> Chacha20ToolBuilder {
+        self.params.algorithm = algorithm;
+        self
+    }
+
+    pub fn iterations(mut self, iterations: u32) -> Chacha20ToolBuilder {
+        self.params.iterations = iterations;
+        self
+    }
+
+    pub fn memory_cost_kib(mut self, memory_kib: u32) -> Chacha20ToolBuilder {
+        self.params.argon2_memory_kib = memory_kib;
+        self
+    }
+
+    pub fn argon2_iterations(mut self, iterations: u32) -> Chacha20ToolBuilder {
+        self.params.argon2_iterations = iterations;
+        self
+    }
+
+    pub fn threads(mut self, threads: u32) -> Chacha20ToolBuilder {
+        self.params.argon2_threads = threads;
+        self
+    }
+
+    pub fn salt_length(mut self, salt_length: u32) -> Chacha20ToolBuilder {
+        self.params.argon2_salt_length = salt_length;
+        self
+    }
+
+    pub fn hash_length(mut self, hash_length: u32) -> Chacha20ToolBuilder {
+        self.params.argon2_hash_length = hash_length;
+        self
+    }
+
+    pub fn cipher(mut self, cipher: CipherId) -> Chacha20ToolBuilder {
+        self.cipher = cipher;
+        self
+    }
+
+    pub fn salt(mut self, salt: &[u8]) -> Chacha20ToolBuilder {
+        self.salt = Some(salt.to_vec());
+        self
+    }
+
+    pub fn build(self, password: &str) -> Result<Chacha20Tool> {
+        let tool = Chacha20Tool::with_params(password, self.params)?.with_cipher(self.cipher);
+        match self.salt {
+            Some(salt) => tool.with_salt(&salt).unlock(),
+            None => tool.unlock(),
+        }
+    }
+}
+
+#[cfg(test)]
+fn key(tool: &Chacha20Tool) -> Result<Data> {
+    tool.expose_key(|key| Data::from(key.as_slice()))
 }
 
 #[test]
@@ -117,3 +346,63 @@ fn test_chacha20tool() -> Result<()> {
     assert_eq!(secret, plaintext);
     Ok(())
 }
+
+#[test]
+fn test_chacha20tool_salt() -> Result<()> {
+    let legacy = Chacha20Tool::new("password", 600)?;
+    let salted = Chacha20Tool::new("password", 600)?.with_salt(b"salt");
+    let other = Chacha20Tool::new("password", 600)?.with_salt(b"other salt");
+    assert_ne!(key(&legacy)?, key(&salted)?);
+    assert_ne!(key(&salted)?, key(&other)?);
+    Ok(())
+}
+
+#[test]
+fn test_chacha20tool_caches_key() -> Result<()> {
+    let tool = Chacha20Tool::new("password", 600)?;
+    assert!(tool.key.get().is_none());
+    let derived = key(&tool)?;
+    assert!(tool.key.get().is_some());
+    assert_eq!(key(&tool)?, derived);
+    let tool = tool.with_salt(b"salt");
+    assert!(tool.key.get().is_none());
+    assert_ne!(key(&tool)?, derived);
+    Ok(())
+}
+
+#[test]
+fn test_chacha20tool_seal() -> Result<()> {
+    let tool = Chacha20Tool::new("password", 600)?;
+    let ciphertext = tool.seal(b"secret", b"name")?;
+    assert_eq!(tool.unseal(&ciphertext, b"name")?, b"secret".to_vec());
+    assert!(matches!(tool.unseal(&ciphertext, b"other"), Err(Error::DecryptionError(_))));
+
+    let mut bytes = ciphertext.ciphertext().to_vec();
+    bytes[0] ^= 0x01;
+    let tampered = CipherText::new(Data::new(bytes), ciphertext.nonce());
+    assert!(matches!(tool.unseal(&tampered, b"name"), Err(Error::DecryptionError(_))));
+    Ok(())
+}
+
+#[test]
+fn test_chacha20tool_builder() -> Result<()> {
+    let builder = Chacha20Tool::builder()
+        .memory_cost_kib(64)
+        .argon2_iterations(1)
+        .threads(1)
+        .salt(b"salt and pepper");
+    let argon2id = builder.clone().algorithm(KdfAlgorithm::Argon2id).build("password")?;
+    let argon2i = builder.clone().algorithm(KdfAlgorithm::Argon2i).build("password")?;
+    let pbkdf2 = builder.clone().algorithm(KdfAlgorithm::Pbkdf2Sha256).build("password")?;
+    assert_eq!(argon2id.params().argon2_memory_kib, 64);
+    assert_ne!(key(&argon2id)?, key(&argon2i)?);
+    assert_ne!(key(&argon2id)?, key(&pbkdf2)?);
+
+    let ciphertext = argon2id.seal(b"secret", b"name")?;
+    assert_eq!(argon2id.unseal(&ciphertext, b"name")?, b"secret".to_vec());
+    assert!(matches!(
+        Chacha20Tool::builder().threads(0).build("password"),
+        Err(Error::InvalidKdfParams(_))
+    ));
+    Ok(())
+}
@@ -1,9 +1,8 @@
-// use std::iter::Iterator;
+use std::iter::Iterator;
 
 use serde::{Deserialize, Serialize};
 
-// use crate::{Data, DataSeq};
-use crate::{Result};
+use crate::{CipherId, Data, DataSeq, Result};
 
 pub trait PlainBytes: for<'a> Deserialize<'a> + Serialize + Sized {
     fn to_bytes(&self) -> Vec<u8> {
@@ -30,24 +29,36 @@ pub trait PlainBytes: for<'a> Deserialize<'a> + Serialize + Sized {
     }
 }
 
+pub trait EncryptionKey {
+    fn cipher(&self) -> CipherId;
+    fn encrypt_bytes(&self, data: &[u8], aad: &[u8]) -> Result<DataSeq>;
+    /// Deterministic keyed digest of `data`, used to look up values
+    /// (such as entry names) without storing them in plaintext.
+    fn blind_index(&self, data: &[u8]) -> Result<Data>;
+    fn encrypt(&self, data: impl Iterator<Item = u8>, aad: &[u8]) -> Result<Data>
+    where
+        Self: Sized,
+    {
+        let data_seq = self.encrypt_bytes(&data.collect::<Vec<u8>>(), aad)?;
+        data_seq.to_data()
+    }
+}
 
-// pub trait EncryptionKey: Clone {
-//     fn encrypt(&self, data: impl Iterator<Item = u8>) -> Result<Data> {
-//         let data_seq = self.encrypt_bytes(&data.collect::<Vec<u8>>())?;
-//         data_seq.to_data()
-//     }
-//     fn encrypt_bytes(&self, data: &[u8]) -> Result<DataSeq>;
-// }
+pub trait DecryptionKey {
+    fn decrypt_bytes(&self, data: DataSeq, aad: &[u8]) -> Result<DataSeq>;
+    fn decrypt(&self, data: impl Iterator<Item = u8>, aad: &[u8]) -> Result<Data>
+    where
+        Self: Sized,
+    {
+        let enc_seq = DataSeq::from_data(&Data::new(data.collect::<Vec<u8>>()))?;
+        let dec_sec = self.decrypt_bytes(enc_seq, aad)?;
+        let mut data = Data::new(Vec::new());
+        for chunk in dec_sec.iter() {
+            data.extend(chunk.iter());
+        }
+        Ok(data)
+    }
+}
 
-// pub trait DecryptionKey: Clone {
-//     fn decrypt(&self, data: impl Iterator<Item = u8>) -> Result<Data> {
-//         let enc_seq = DataSeq::from_data(&Data::new(data.collect::<Vec<u8>>()))?;
-//         let dec_sec = self.decrypt_bytes(enc_seq)?;
-//         let mut data = Data::new(Vec::new());
-//         for chunk in dec_sec.iter() {
-//             data.extend(chunk.iter());
-//         }
-//         Ok(data)
-//     }
-//     fn decrypt_bytes(&self, data: DataSeq) -> Result<DataSeq>;
-// }
+pub trait Cipher: EncryptionKey + DecryptionKey {}
+impl<T: EncryptionKey + DecryptionKey> Cipher for T {}
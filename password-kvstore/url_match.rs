@@ -0,0 +1,250 @@
+//! Matching of [`Entry::urls`](crate::Entry::urls) against the URL of a
+//! site, see [`Folder::match_url`](crate::Folder::match_url).
+//!
+//! Every URL of an entry is matched according to its [`UrlMatchMode`],
+//! kept in [`Entry::url_modes`](crate::Entry::url_modes) and defaulting
+//! to [`Domain`](UrlMatchMode::Domain). Entry URLs without a scheme, such
+//! as `github.com`, are read as `https://` URLs.
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::{Entry, Error, Result};
+
+/// How an entry URL is compared with the URL of a site, from the
+/// loosest to the strictest.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum UrlMatchMode {
+    /// Same registrable domain, e.g. `login.example.com` for
+    /// `www.example.com`, see [`registrable_domain`].
+    #[default]
+    Domain,
+    /// Same host, any scheme or port.
+    Host,
+    /// Same scheme, host and port.
+    Origin,
+    /// The site URL starts with the entry URL, which has a path or query
+    /// beyond its origin, at a path segment boundary: `/login` matches
+    /// `/login/otp` but not `/login-help`.
+    Prefix,
+    /// The entry URL is a regular expression the whole site URL must
+    /// match.
+    Regex,
+}
+impl UrlMatchMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            UrlMatchMode::Domain => "domain",
+            UrlMatchMode::Host => "host",
+            UrlMatchMode::Origin => "origin",
+            UrlMatchMode::Prefix => "prefix",
+            UrlMatchMode::Regex => "regex",
+        }
+    }
+}
+impl std::str::FromStr for UrlMatchMode {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<UrlMatchMode> {
+        match name.to_ascii_lowercase().as_str() {
+            "domain" => Ok(UrlMatchMode::Domain),
+            "host" => Ok(UrlMatchMode::Host),
+            "origin" => Ok(UrlMatchMode::Origin),
+            "prefix" => Ok(UrlMatchMode::Prefix),
+            "regex" => Ok(UrlMatchMode::Regex),
+            _ => Err(Error::InvalidFormat(format!("unknown url match mode {:#?}", name))),
+        }
+    }
+}
+
+/// An entry matched by [`Folder::match_url`](crate::Folder::match_url),
+/// the entry URL that matched and its score, higher first.
+#[derive(Debug, PartialEq, Eq)]
+pub struct UrlMatch {
+    pub entry: Entry,
+    pub url: String,
+    pub score: u32,
+}
+
+/// Closest relation between an entry URL and a site URL, from the
+/// loosest to the strictest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Relation {
+    Domain,
+    Host,
+    Origin,
+    Prefix,
+    Exact,
+}
+impl Relation {
+    fn score(&self) -> u32 {
+        match self {
+            Relation::Domain => 20,
+            Relation::Host => 40,
+            Relation::Origin => 60,
+            Relation::Prefix => 80,
+            Relation::Exact => 100,
+        }
+    }
+
+    fn satisfies(&self, mode: UrlMatchMode) -> bool {
+        match mode {
+            UrlMatchMode::Domain => true,
+            UrlMatchMode::Host => *self >= Relation::Host,
+            UrlMatchMode::Origin => *self >= Relation::Origin,
+            UrlMatchMode::Prefix => *self >= Relation::Prefix,
+            UrlMatchMode::Regex => false,
+        }
+    }
+}
+
+/// Score of a regular expression match, between a host and an origin
+/// match.
+const REGEX_SCORE: u32 = 50;
+
+/// Parses an entry URL, reading it as an `https://` URL when it has no
+/// scheme.
+pub fn parse_entry_url(url: &str) -> Result<Url> {
+    match Url::parse(url) {
+        Ok(parsed) if parsed.has_host() => Ok(parsed),
+        _ => Url::parse(&format!("https://{}", url))
+            .ok()
+            .filter(Url::has_host)
+            .ok_or_else(|| Error::InvalidFormat(format!("invalid url {:#?}", url))),
+    }
+}
+
+/// Registrable domain of `host` according to the Public Suffix List:
+/// its public suffix plus one label. `None` for IP addresses, public
+/// suffixes themselves and hosts whose suffix is not on the list, which
+/// then only match by host.
+pub fn registrable_domain(host: &str) -> Option<String> {
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    if host.parse::<std::net::IpAddr>().is_ok() || host.starts_with('[') {
+        return None;
+    }
+    let domain = psl::domain(host.as_bytes()).filter(|domain| domain.suffix().is_known())?;
+    String::from_utf8(domain.as_bytes().to_vec()).ok()
+}
+
+/// Whether `url` starts with `prefix` and continues, if at all, with a
+/// new path segment, query or fragment.
+fn is_prefix(prefix: &str, url: &str) -> bool {
+    match url.strip_prefix(prefix) {
+        Some(rest) => prefix.ends_with('/') || rest.is_empty() || rest.starts_with(['/', '?', '#']),
+        None => false,
+    }
+}
+
+fn relation(entry_url: &Url, url: &Url) -> Option<Relation> {
+    let (entry_host, host) = (entry_url.host_str()?, url.host_str()?);
+    if entry_url.as_str().trim_end_matches('/') == url.as_str().trim_end_matches('/') {
+        Some(Relation::Exact)
+    } else if entry_url.origin() == url.origin()
+        && (entry_url.path() != "/" || entry_url.query().is_some())
+        && is_prefix(entry_url.as_str(), url.as_str())
+    {
+        Some(Relation::Prefix)
+    } else if entry_url.origin() == url.origin() {
+        Some(Relation::Origin)
+    } else if entry_host.eq_ignore_ascii_case(host) {
+        Some(Relation::Host)
+    } else if registrable_domain(entry_host)
+        .is_some_and(|domain| registrable_domain(host).is_some_and(|other| domain == other))
+    {
+        Some(Relation::Domain)
+    } else {
+        None
+    }
+}
+
+/// Score of the entry URL `pattern` under `mode` against `url`, `None`
+/// when it does not match.
+pub fn score(pattern: &str, mode: UrlMatchMode, url: &Url) -> Result<Option<u32>> {
+    if mode == UrlMatchMode::Regex {
+        let regex = compile(pattern)?;
+        return Ok(regex.is_match(url.as_str()).then_some(REGEX_SCORE));
+    }
+    let entry_url = parse_entry_url(pattern)?;
+    Ok(relation(&entry_url, url)
+        .filter(|relation| relation.satisfies(mode))
+        .map(|relation| relation.score()))
+}
+
+/// Compiles `pattern` anchored to the whole URL.
+pub(crate) fn compile(pattern: &str) -> Result<regex::Regex> {
+    regex::Regex::new(&format!("^(?:{})$", pattern))
+        .map_err(|e| Error::InvalidFormat(format!("invalid url regex {:#?}: {}", pattern, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use url::Url;
+
+    use super::{registrable_domain, score};
+    use crate::{Result, UrlMatchMode};
+
+    #[test]
+    fn test_registrable_domain() {
+        for (host, domain) in [
+            ("www.example.com", Some("example.com")),
+            ("example.com", Some("example.com")),
+            ("a.b.example.co.uk", Some("example.co.uk")),
+            ("shop.example.com.pl", Some("example.com.pl")),
+            ("Example.COM.", Some("example.com")),
+            ("alice.github.io", Some("alice.github.io")),
+            ("www.alice.blogspot.com", Some("alice.blogspot.com")),
+            ("files.bucket.s3.amazonaws.com", Some("bucket.s3.amazonaws.com")),
+            ("co.uk", None),
+            ("co.th", None),
+            ("localhost", None),
+            ("intranet.corp", None),
+            ("192.168.0.1", None),
+        ] {
+            assert_eq!(registrable_domain(host).as_deref(), domain, "{}", host);
+        }
+    }
+
+    #[test]
+    fn test_url_match_score() -> Result<()> {
+        let url = Url::parse("https://login.example.com/auth/sign-in?next=/")?;
+        for (pattern, mode, expected) in [
+            ("example.com", UrlMatchMode::Domain, Some(20)),
+            ("https://www.example.com", UrlMatchMode::Domain, Some(20)),
+            ("https://www.example.com", UrlMatchMode::Host, None),
+            ("http://login.example.com", UrlMatchMode::Host, Some(40)),
+            ("http://login.example.com", UrlMatchMode::Origin, None),
+            ("https://login.example.com/account", UrlMatchMode::Origin, Some(60)),
+            ("https://login.example.com/auth/", UrlMatchMode::Prefix, Some(80)),
+            ("https://login.example.com/account", UrlMatchMode::Prefix, None),
+            ("https://login.example.com/auth", UrlMatchMode::Prefix, Some(80)),
+            ("https://login.example.com/auth/sign", UrlMatchMode::Prefix, None),
+            ("https://login.example.com/auth/sign-in?next", UrlMatchMode::Prefix, None),
+            (
+                "https://login.example.com/auth/sign-in?next=/",
+                UrlMatchMode::Prefix,
+                Some(100),
+            ),
+            ("login.example.com/auth/sign-in?next=/", UrlMatchMode::Domain, Some(100)),
+            (r"https://[a-z]+\.example\.com/auth/.*", UrlMatchMode::Regex, Some(50)),
+            (r"example\.com", UrlMatchMode::Regex, None),
+            ("example.org", UrlMatchMode::Domain, None),
+        ] {
+            assert_eq!(score(pattern, mode, &url)?, expected, "{} {:?}", pattern, mode);
+        }
+        for (pattern, url, expected) in [
+            ("alice.blogspot.com", "https://mallory.blogspot.com", None),
+            ("shop.com.pl", "https://evil.com.pl", None),
+            ("wiki.intranet.corp", "https://mail.intranet.corp", None),
+            ("wiki.intranet.corp", "http://wiki.intranet.corp/login", Some(40)),
+        ] {
+            assert_eq!(
+                score(pattern, UrlMatchMode::Domain, &Url::parse(url)?)?,
+                expected,
+                "{}",
+                url
+            );
+        }
+        assert!(score("(", UrlMatchMode::Regex, &url).is_err());
+        Ok(())
+    }
+}
@@ -4,6 +4,7 @@ use flate2::read::DeflateDecoder;
 use flate2::write::DeflateEncoder;
 use flate2::Compression;
 use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, Zeroizing};
 
 use crate::errors::Result;
 
@@ -38,7 +39,7 @@ pub fn scrub_with_byte(data: &mut Vec<u8>, byte: u8) {
     }
 }
 pub fn zerofill(data: &mut Vec<u8>) {
-    scrub_with_byte(data, 0);
+    data.as_mut_slice().zeroize();
 }
 
 pub fn discharge(data: &mut Vec<u8>) {
@@ -70,10 +71,14 @@ pub fn to_flate_bytes<T: Serialize>(data: &T) -> Result<Vec<u8>> {
 }
 
 pub fn from_deflate_bytes<T: for<'a> Deserialize<'a>>(bytes: &[u8]) -> Result<T> {
+    Ok(bincode::deserialize::<T>(&inflate(bytes)?)?)
+}
+
+pub(crate) fn inflate(bytes: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
     let mut d = DeflateDecoder::new(bytes);
-    let mut bytes = Vec::<u8>::with_capacity(bytes.len());
-    d.read_to_end(&mut bytes)?;
-    Ok(bincode::deserialize::<T>(&bytes)?)
+    let mut inflated = Zeroizing::new(Vec::<u8>::with_capacity(bytes.len()));
+    d.read_to_end(&mut inflated)?;
+    Ok(inflated)
 }
 
 pub fn chunk_padded(items: &[u8], chunk_size: usize, padding: u8) -> Vec<Vec<u8>> {
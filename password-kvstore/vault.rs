@@ -0,0 +1,631 @@
+use std::collections::BTreeMap;
+use std::io::{BufReader, BufWriter, Read, Write};
+
+use iocore::Path;
+
+use crate::{
+    folder, Attachment, Chacha20Tool, CipherId, DataSeq, DecryptionKey, EncryptionKey, Entry,
+    EntryMetadata, Error, Folder, Header, KdfParams, PlainBytes, Result, StoredFolder,
+    DEFAULT_KDF_MEMORY_KIB, DEFAULT_KDF_TARGET, PATH_SEPARATOR,
+};
+
+pub struct Vault {
+    path: Path,
+    header: Header,
+    folders: BTreeMap<String, Folder>,
+    tool: Chacha20Tool,
+    removed_attachments: Vec<Attachment>,
+}
+impl Vault {
+    /// Creates a vault whose KDF is calibrated with [`DEFAULT_KDF_TARGET`]
+    /// and [`DEFAULT_KDF_MEMORY_KIB`], see [`KdfParams::calibrate`].
+    pub fn create(path: impl Into<Path>, password: &str) -> Result<Vault> {
+        let params = KdfParams::calibrate(DEFAULT_KDF_TARGET, DEFAULT_KDF_MEMORY_KIB)?;
+        Vault::create_with_params(path, password, params)
+    }
+
+    pub fn create_with_params(
+        path: impl Into<Path>,
+        password: &str,
+        params: KdfParams,
+    ) -> Result<Vault> {
+        Vault::create_with_cipher(path, password, params, CipherId::default())
+    }
+
+    pub fn create_with_cipher(
+        path: impl Into<Path>,
+        password: &str,
+        params: KdfParams,
+        cipher: CipherId,
+    ) -> Result<Vault> {
+        let path = path.into();
+        if path.exists() {
+            return Err(Error::AlreadyExists(format!(
+                "vault {:#?} already exists",
+                path.to_string()
+            )));
+        }
+        let mut header = Header::new(params);
+        header.cipher = cipher;
+        let mut vault = Vault {
+            path,
+            tool: Vault::unlock(password, &header)?,
+            header,
+            folders: BTreeMap::new(),
+            removed_attachments: Vec::new(),
+        };
+        vault.save()?;
+        Ok(vault)
+    }
+
+    pub fn open(path: impl Into<Path>, password: &str) -> Result<Vault> {
+        let path = path.into();
+        if !path.is_file() {
+            return Err(Error::NotFound(format!("no vault found at {:#?}", path.to_string())));
+        }
+        let bytes = path.read_bytes()?;
+        let (header, payload) = Header::decode(&bytes)?;
+        let tool = Vault::unlock(password, &header)?;
+        let aad = header.to_plain_bytes();
+        let plaintext = match tool.decrypt_bytes(DataSeq::from_plain_bytes(payload)?, &aad) {
+            Ok(plaintext) => plaintext[0].to_vec(),
+            Err(Error::DecryptionError(_)) =>
+                return Err(Error::DecryptionError(format!(
+                    "could not unlock vault {:#?}",
+                    path.to_string()
+                ))),
+            Err(error) => return Err(error),
+        };
+        let folders = Vault::decode_folders(&plaintext, &tool)?;
+        Ok(Vault {
+            path,
+            header,
+            folders,
+            tool,
+            removed_attachments: Vec::new(),
+        })
+    }
+
+    fn unlock(password: &str, header: &Header) -> Result<Chacha20Tool> {
+        Ok(Chacha20Tool::with_params(password, header.kdf.clone())?
+            .with_cipher(header.cipher)
+            .with_salt(&header.salt))
+    }
+
+    fn decode_folders(plaintext: &[u8], tool: &Chacha20Tool) -> Result<BTreeMap<String, Folder>> {
+        crate::from_deflate_bytes::<BTreeMap<String, StoredFolder>>(plaintext)?
+            .into_iter()
+            .map(|(name, folder)| Ok((name, Folder::from_stored(folder, tool)?)))
+            .collect()
+    }
+
+    pub fn change_password(&mut self, password: &str) -> Result<()> {
+        self.rekey(password, self.header.kdf.clone())
+    }
+
+    pub fn rekey(&mut self, password: &str, params: KdfParams) -> Result<()> {
+        let mut header = Header::new(params);
+        header.cipher = self.header.cipher;
+        let tool = Vault::unlock(password, &header)?;
+        let folders = self
+            .folders
+            .iter()
+            .map(|(name, folder)| Ok((name.to_string(), folder.rekeyed(&self.tool, &tool)?)))
+            .collect::<Result<BTreeMap<String, Folder>>>()?;
+        Vault::write(&self.path, &header, &folders, &tool)?;
+        self.header = header;
+        self.folders = folders;
+        self.tool = tool;
+        self.purge_attachments()
+    }
+
+    /// Writes the vault, then deletes the files of the attachments removed
+    /// since the last save.
+    pub fn save(&mut self) -> Result<()> {
+        Vault::write(&self.path, &self.header, &self.folders, &self.tool)?;
+        self.purge_attachments()
+    }
+
+    fn write(
+        path: &Path,
+        header: &Header,
+        folders: &BTreeMap<String, Folder>,
+        tool: &Chacha20Tool,
+    ) -> Result<()> {
+        let folders = folders
+            .iter()
+            .map(|(name, folder)| Ok((name.to_string(), folder.to_stored(tool)?)))
+            .collect::<Result<BTreeMap<String, StoredFolder>>>()?;
+        let plaintext = crate::to_flate_bytes(&folders)?;
+        let ciphertext = tool.encrypt_bytes(&plaintext, &header.to_plain_bytes())?;
+        let bytes = header.encode(&ciphertext.to_plain_bytes())?;
+        let partial = path.with_filename(format!(".{}.partial", path.name()));
+        if partial.exists() {
+            partial.delete()?;
+        }
+        let mut file = create_private(&partial)?;
+        file.write_all(&bytes)?;
+        file.sync_all()?;
+        partial.rename(path.to_string(), false)?;
+        if let Some(parent) = path.parent() {
+            std::fs::File::open(parent.path())?.sync_all()?;
+        }
+        Ok(())
+    }
+
+    pub fn close(mut self) -> Result<()> {
+        self.save()
+    }
+
+    pub fn path(&self) -> Path {
+        self.path.clone()
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    pub fn tool(&self) -> &Chacha20Tool {
+        &self.tool
+    }
+
+    /// Names of the top-level folders, see [`folder_paths`](Vault::folder_paths)
+    /// for every folder.
+    pub fn folder_names(&self) -> Vec<String> {
+        self.folders.keys().cloned().collect()
+    }
+
+    /// Paths of every folder, recursively, parents first.
+    pub fn folder_paths(&self) -> Vec<String> {
+        folder::folder_paths(&self.folders)
+    }
+
+    /// Paths of every entry in every folder, as in `"infra/aws/root"`.
+    pub fn entry_paths(&self) -> Vec<String> {
+        let mut paths = Vec::new();
+        for (name, folder) in &self.folders {
+            paths.extend(
+                folder
+                    .entry_paths()
+                    .into_iter()
+                    .map(|path| format!("{}{}{}", name, PATH_SEPARATOR, path)),
+            );
+        }
+        paths
+    }
+
+    pub fn folders(&self) -> impl Iterator<Item = &Folder> {
+        self.folders.values()
+    }
+
+    /// Creates the folder at `path` along with any missing parents.
+    pub fn add_folder(&mut self, path: &str) -> Result<&mut Folder> {
+        folder::add_folder(&mut self.folders, path)
+    }
+
+    pub fn folder(&self, path: &str) -> Result<&Folder> {
+        folder::find_folder(&self.folders, path)
+    }
+
+    pub fn folder_mut(&mut self, path: &str) -> Result<&mut Folder> {
+        folder::find_folder_mut(&mut self.folders, path)
+    }
+
+    /// Removes the folder at `path` along with its subfolders. The files
+    /// of their attachments are deleted by the next [`save`](Vault::save).
+    pub fn remove_folder(&mut self, path: &str) -> Result<Folder> {
+        let attachments = self.folder(path)?.attachments(&self.tool)?;
+        let folder = folder::remove_folder(&mut self.folders, path)?;
+        self.removed_attachments.extend(attachments);
+        Ok(folder)
+    }
+
+    /// Moves the folder at `from`, subfolders included, to `to`. Entries
+    /// are not re-encrypted.
+    pub fn rename_folder(&mut self, from: &str, to: &str) -> Result<()> {
+        folder::rename_folder(&mut self.folders, from, to)
+    }
+
+    /// Moves the entry at path `from`, as in `"infra/aws/root"`, into the
+    /// folder at `to`. The entry is only re-encrypted when exactly one of
+    /// the two folders uses [`blind_names`](Folder::blind_names).
+    pub fn move_entry(&mut self, from: &str, to: &str) -> Result<()> {
+        folder::move_entry(&mut self.folders, from, to, &self.tool)
+    }
+
+    pub fn add_entry(&mut self, folder: &str, entry: Entry) -> Result<Entry> {
+        let tool = &self.tool;
+        folder::find_folder_mut(&mut self.folders, folder)?.add_entry(entry, tool)
+    }
+
+    pub fn update_entry(&mut self, folder: &str, entry: Entry) -> Result<Entry> {
+        let tool = &self.tool;
+        folder::find_folder_mut(&mut self.folders, folder)?.update_entry(entry, tool)
+    }
+
+    pub fn get_entry(&self, folder: &str, name: &str) -> Result<Entry> {
+        self.folder(folder)?.get(name, &self.tool)
+    }
+
+    /// Records an access to the entry, see [`Folder::touch`].
+    pub fn touch_entry(&mut self, folder: &str, name: &str) -> Result<Entry> {
+        let tool = &self.tool;
+        folder::find_folder_mut(&mut self.folders, folder)?.touch(name, tool)
+    }
+
+    /// Metadata of the entries of the folder at `folder`, read from its
+    /// index.
+    pub fn metadata(&self, folder: &str) -> Result<Vec<EntryMetadata>> {
+        Ok(self.folder(folder)?.metadata())
+    }
+
+    /// Entry at `path`, as in `"infra/aws/root"`.
+    pub fn get_entry_at(&self, path: &str) -> Result<Entry> {
+        match folder::split_parent(path)? {
+            (Some(folder), name) => self.get_entry(folder, name),
+            (None, _) => Err(Error::InvalidFormat(format!("invalid entry path {:#?}", path))),
+        }
+    }
+
+    /// Deletes the entry. The files of its attachments are deleted by the
+    /// next [`save`](Vault::save).
+    pub fn delete_entry(&mut self, folder: &str, name: &str) -> Result<bool> {
+        let entry = self.get_entry(folder, name)?;
+        let tool = &self.tool;
+        let deleted = folder::find_folder_mut(&mut self.folders, folder)?.delete(name, tool)?;
+        self.removed_attachments.extend(entry.attachments);
+        Ok(deleted)
+    }
+
+    /// Directory next to the vault file holding the encrypted attachments.
+    pub fn attachments_path(&self) -> Path {
+        self.path.with_filename(format!("{}.attachments", self.path.name()))
+    }
+
+    fn attachment_path(&self, attachment: &Attachment) -> Path {
+        self.attachments_path().join(&attachment.id)
+    }
+
+    fn delete_attachment_file(&self, attachment: &Attachment) -> Result<()> {
+        let path = self.attachment_path(attachment);
+        if path.exists() {
+            path.delete()?;
+        }
+        Ok(())
+    }
+
+    /// Deletes the files of removed attachments, once no saved entry
+    /// refers to them anymore.
+    fn purge_attachments(&mut self) -> Result<()> {
+        while let Some(attachment) = self.removed_attachments.last() {
+            self.delete_attachment_file(attachment)?;
+            self.removed_attachments.pop();
+        }
+        Ok(())
+    }
+
+    /// Encrypts everything `reader` yields into a new file under
+    /// [`attachments_path`](Vault::attachments_path) and records it on
+    /// the entry as attachment `name`, streaming in chunks of
+    /// [`ATTACHMENT_CHUNK_SIZE`](crate::ATTACHMENT_CHUNK_SIZE) bytes.
+    ///
+    /// The file is written right away; the entry that references it is
+    /// persisted with the next [`save`](Vault::save).
+    pub fn add_attachment(
+        &mut self,
+        folder: &str,
+        entry: &str,
+        name: &str,
+        reader: impl Read,
+    ) -> Result<Attachment> {
+        let mut entry = self.get_entry(folder, entry)?;
+        if entry.attachment(name).is_ok() {
+            return Err(Error::AlreadyExists(format!(
+                "entry {:#?} already has an attachment {:#?}",
+                entry.name, name
+            )));
+        }
+        let directory = self.attachments_path();
+        if !directory.exists() {
+            directory.mkdir()?;
+        }
+        let partial = directory.join(format!(".{:x}.partial", rand::random::<u64>()));
+        let mut writer = BufWriter::new(create_private(&partial)?);
+        let attachment = match Attachment::encrypt(name, reader, &mut writer) {
+            Ok(attachment) => attachment,
+            Err(error) => {
+                drop(writer);
+                partial.delete()?;
+                return Err(error);
+            },
+        };
+        writer.into_inner().map_err(|e| Error::IOError(format!("{}", e)))?.sync_all()?;
+        let path = self.attachment_path(&attachment);
+        partial.rename(path.to_string(), false)?;
+        entry.attachments.push(attachment);
+        let mut entry = match self.update_entry(folder, entry) {
+            Ok(entry) => entry,
+            Err(error) => {
+                path.delete()?;
+                return Err(error);
+            },
+        };
+        entry.attachments.pop().ok_or_else(|| {
+            Error::InvalidEntry(format!("entry {:#?} lost its attachment {:#?}", entry.name, name))
+        })
+    }
+
+    /// Decrypts attachment `name` of the entry into `writer`, chunk by
+    /// chunk, returning the number of bytes written.
+    pub fn read_attachment(
+        &self,
+        folder: &str,
+        entry: &str,
+        name: &str,
+        writer: impl Write,
+    ) -> Result<u64> {
+        let entry = self.get_entry(folder, entry)?;
+        let attachment = entry.attachment(name)?;
+        let path = self.attachment_path(attachment);
+        if !path.is_file() {
+            return Err(Error::NotFound(format!(
+                "attachment {:#?} is missing from {:#?}",
+                name,
+                self.attachments_path().to_string()
+            )));
+        }
+        attachment.decrypt(BufReader::new(std::fs::File::open(path.path())?), writer)
+    }
+
+    /// Removes attachment `name` from the entry. Its file is deleted by
+    /// the next [`save`](Vault::save).
+    pub fn remove_attachment(&mut self, folder: &str, entry: &str, name: &str) -> Result<()> {
+        let mut entry = self.get_entry(folder, entry)?;
+        let id = entry.attachment(name)?.id.to_string();
+        let (removed, kept): (Vec<Attachment>, Vec<Attachment>) =
+            std::mem::take(&mut entry.attachments)
+                .into_iter()
+                .partition(|other| other.id == id);
+        entry.attachments = kept;
+        self.update_entry(folder, entry)?;
+        self.removed_attachments.extend(removed);
+        Ok(())
+    }
+
+    pub fn set_blind_names(&mut self, folder: &str, enabled: bool) -> Result<()> {
+        let tool = &self.tool;
+        folder::find_folder_mut(&mut self.folders, folder)?.set_blind_names(enabled, tool)
+    }
+}
+
+/// Creates a new file at `path` that only its owner can read and write,
+/// failing if anything already exists there.
+fn create_private(path: &Path) -> Result<std::fs::File> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    Ok(options.open(path.path())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use iocore::Path;
+
+    use crate::{
+        CipherId, EncryptionKey, Entry, Error, KdfParams, PlainBytes, Result, Secret, Vault,
+    };
+
+    fn create(path: &Path) -> Result<Vault> {
+        Vault::create_with_params(path, "password", KdfParams::with_iterations(600))
+    }
+
+    #[test]
+    fn test_vault() -> Result<()> {
+        let path = Path::tmp().join("test.vault");
+        let mut vault = create(&path)?;
+        let mut entry = Entry::new("entry");
+        entry.password = Secret::from("entry");
+        vault.add_folder("folder")?;
+        let entry = vault.add_entry("folder", entry)?;
+        path.with_filename(".test.vault.partial").write(b"stale")?;
+        vault.close()?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(path.path())?.permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let vault = Vault::open(&path, "password")?;
+        assert_eq!(vault.folder_names(), vec!["folder".to_string()]);
+        assert_eq!(vault.get_entry("folder", "entry")?, entry);
+        assert!(matches!(Vault::create(&path, "password"), Err(Error::AlreadyExists(_))));
+        assert!(matches!(Vault::open(&path, "wrong"), Err(Error::DecryptionError(_))));
+        path.parent().unwrap().delete()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_vault_open_errors() -> Result<()> {
+        let path = Path::tmp().join("errors.vault");
+        let vault = create(&path)?;
+        let aad = vault.header().to_plain_bytes();
+        let ciphertext = vault.tool.encrypt_bytes(b"not a folder map", &aad)?;
+        let bytes = vault.header().encode(&ciphertext.to_plain_bytes())?;
+        path.write(&bytes)?;
+        assert!(matches!(Vault::open(&path, "password"), Err(Error::IOError(_))));
+
+        path.write(&bytes[..12])?;
+        assert!(matches!(Vault::open(&path, "password"), Err(Error::InvalidFormat(_))));
+        let mut bytes = bytes;
+        bytes[8..10].copy_from_slice(&(crate::FORMAT_VERSION + 1).to_le_bytes());
+        path.write(&bytes)?;
+        assert!(matches!(Vault::open(&path, "password"), Err(Error::UnsupportedVersion(_))));
+        path.parent().unwrap().delete()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_vault_change_password() -> Result<()> {
+        let path = Path::tmp().join("rekey.vault");
+        let mut vault = create(&path)?;
+        vault.add_folder("folder")?;
+        let entry = vault.add_entry("folder", Entry::from("entry"))?;
+        vault.save()?;
+        let salt = vault.header().salt.clone();
+
+        vault.change_password("new password")?;
+        assert_ne!(vault.header().salt, salt);
+        assert_eq!(vault.get_entry("folder", "entry")?, entry);
+        assert_eq!(path.parent().unwrap().list()?, vec![path.clone()]);
+
+        assert!(matches!(Vault::open(&path, "password"), Err(Error::DecryptionError(_))));
+        let vault = Vault::open(&path, "new password")?;
+        assert_eq!(vault.get_entry("folder", "entry")?, entry);
+        path.parent().unwrap().delete()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_vault_records_cipher() -> Result<()> {
+        let path = Path::tmp().join("aes.vault");
+        let mut vault = Vault::create_with_cipher(
+            &path,
+            "password",
+            KdfParams::with_iterations(600),
+            CipherId::Aes256GcmSiv,
+        )?;
+        vault.add_folder("folder")?;
+        vault.add_entry("folder", Entry::from("entry"))?;
+        vault.close()?;
+
+        let vault = Vault::open(&path, "password")?;
+        assert_eq!(vault.header().cipher, CipherId::Aes256GcmSiv);
+        assert_eq!(vault.get_entry("folder", "entry")?.name, "entry");
+        path.parent().unwrap().delete()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_vault_blind_names() -> Result<()> {
+        let path = Path::tmp().join("blind.vault");
+        let mut vault = create(&path)?;
+        vault.add_folder("folder")?;
+        vault.add_entry("folder", Entry::from("payroll-bank"))?;
+        vault.set_blind_names("folder", true)?;
+        vault.close()?;
+
+        let mut vault = Vault::open(&path, "password")?;
+        assert!(vault.folder("folder")?.blind_names);
+        assert_eq!(vault.get_entry("folder", "payroll-bank")?.name, "payroll-bank");
+        vault.change_password("new password")?;
+        assert!(vault.delete_entry("folder", "payroll-bank")?);
+        path.parent().unwrap().delete()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_vault_attachments() -> Result<()> {
+        let path = Path::tmp().join("attachments.vault");
+        let mut vault = create(&path)?;
+        vault.add_folder("folder")?;
+        vault.add_entry("folder", Entry::from("entry"))?;
+        let bundle =
+            (0..3 * crate::ATTACHMENT_CHUNK_SIZE + 7).map(|n| n as u8).collect::<Vec<u8>>();
+        let attachment =
+            vault.add_attachment("folder", "entry", "client.p12", bundle.as_slice())?;
+        vault.add_attachment("folder", "entry", "codes.pdf", b"recovery codes".as_slice())?;
+        assert!(matches!(
+            vault.add_attachment("folder", "entry", "codes.pdf", b"".as_slice()),
+            Err(Error::AlreadyExists(_))
+        ));
+        let stored = vault.attachments_path().join(&attachment.id).read_bytes()?;
+        assert!(!stored.windows(64).any(|window| window == &bundle[1024..1088]));
+        vault.close()?;
+
+        let mut vault = Vault::open(&path, "password")?;
+        let mut contents = Vec::new();
+        assert_eq!(
+            vault.read_attachment("folder", "entry", "client.p12", &mut contents)?,
+            bundle.len() as u64
+        );
+        assert_eq!(contents, bundle);
+        vault.remove_attachment("folder", "entry", "client.p12")?;
+        assert!(matches!(
+            vault.read_attachment("folder", "entry", "client.p12", std::io::sink()),
+            Err(Error::NotFound(_))
+        ));
+        assert_eq!(vault.attachments_path().list()?.len(), 2);
+        vault.save()?;
+        assert_eq!(vault.attachments_path().list()?.len(), 1);
+
+        vault.add_folder("folder/sub")?;
+        vault.add_entry("folder/sub", Entry::from("other"))?;
+        vault.add_attachment("folder/sub", "other", "key.pem", b"key".as_slice())?;
+        vault.delete_entry("folder", "entry")?;
+        assert_eq!(vault.attachments_path().list()?.len(), 2);
+        vault.save()?;
+        assert_eq!(vault.attachments_path().list()?.len(), 1);
+        vault.remove_folder("folder")?;
+        vault.close()?;
+        let vault = Vault::open(&path, "password")?;
+        assert!(vault.attachments_path().list()?.is_empty());
+        path.parent().unwrap().delete()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_vault_nested_folders() -> Result<()> {
+        let path = Path::tmp().join("nested.vault");
+        let mut vault = create(&path)?;
+        vault.add_folder("infra/aws")?;
+        vault.add_folder("infra/gcp")?;
+        vault.add_folder("personal")?;
+        vault.add_entry("infra/aws", Entry::from("root"))?;
+        vault.add_entry("infra", Entry::from("vpn"))?;
+        vault.set_blind_names("personal", true)?;
+        assert!(matches!(vault.add_folder("infra/aws"), Err(Error::AlreadyExists(_))));
+        assert!(matches!(vault.add_folder("infra//aws"), Err(Error::InvalidFormat(_))));
+        vault.close()?;
+
+        let mut vault = Vault::open(&path, "password")?;
+        assert_eq!(vault.folder_names(), vec!["infra", "personal"]);
+        assert_eq!(vault.folder_paths(), vec!["infra", "infra/aws", "infra/gcp", "personal"]);
+        assert_eq!(vault.entry_paths(), vec!["infra/vpn", "infra/aws/root"]);
+        assert_eq!(vault.get_entry_at("infra/aws/root")?.name, "root");
+
+        let sealed = vault.folder("infra/aws")?.entries["root"].open()?;
+        vault.move_entry("infra/aws/root", "infra/gcp")?;
+        assert_eq!(vault.folder("infra/gcp")?.entries["root"].open()?, sealed);
+        assert!(matches!(vault.get_entry("infra/aws", "root"), Err(Error::NotFound(_))));
+        vault.move_entry("infra/gcp/root", "personal")?;
+        assert_eq!(vault.get_entry_at("personal/root")?.name, "root");
+        vault.add_entry("infra/gcp", Entry::from("root"))?;
+        assert!(matches!(
+            vault.move_entry("personal/root", "infra/gcp"),
+            Err(Error::AlreadyExists(_))
+        ));
+        assert_eq!(vault.get_entry_at("personal/root")?.name, "root");
+
+        let sealed = vault.folder("infra")?.entries["vpn"].open()?;
+        vault.rename_folder("infra", "work/infrastructure")?;
+        assert!(matches!(
+            vault.rename_folder("work", "work/infrastructure/work"),
+            Err(Error::InvalidFormat(_))
+        ));
+        assert_eq!(vault.folder("work/infrastructure")?.entries["vpn"].open()?, sealed);
+        vault.close()?;
+
+        let mut vault = Vault::open(&path, "password")?;
+        assert_eq!(
+            vault.entry_paths(),
+            vec!["personal/root", "work/infrastructure/vpn", "work/infrastructure/gcp/root"]
+        );
+        assert_eq!(vault.get_entry_at("work/infrastructure/vpn")?.name, "vpn");
+        vault.remove_folder("work/infrastructure")?;
+        assert_eq!(vault.folder_paths(), vec!["personal", "work"]);
+        path.parent().unwrap().delete()?;
+        Ok(())
+    }
+}